@@ -6,7 +6,7 @@ extern crate inferno;
 #[path = "data/teapot.rs"]
 mod teapot;
 
-use inferno::resources::{ResourceManager, Font};
+use inferno::resources::ResourceManager;
 use inferno::rendering::{DrawBatch, ColorVertex2d, ColorVertex};
 use inferno::gui::{EventListener, Image, Text, Window, Button, Docks};
 
@@ -23,12 +23,11 @@ fn main() {
     let inferno_logo_texture = resource_manager.create_texture("example_images/inferno-logo2.png").unwrap();
 
     let gui_font = resource_manager.create_font("DejaVuSans.ttf", 14).unwrap();
-    gui_font.load_glyphs();
+    resource_manager.resolve_font(gui_font).unwrap().load_glyph_range(' ', '~').unwrap();
 
     //let font = resource_manager.create_font("Gudea-Regular.ttf", 28).unwrap();
     let font = resource_manager.create_font("DejaVuSans.ttf", 28).unwrap();
-    font.load_glyphs();
-    let mut text = Text::new(font.clone(), "Inferno Test");
+    let mut text = Text::new(&resource_manager, font, "Inferno Test");
     text.set_position(650.0, -150.0);
     text.set_color(255, 255, 0, 255);
 
@@ -45,11 +44,11 @@ fn main() {
         teapot_indices.push(*i as u32);
     }
 
-    let inferno_logo = Image::new(inferno_logo_texture.clone(), 650.0, 0.0, 128.0, 128.0);
+    let inferno_logo = Image::new(inferno_logo_texture, 650.0, 0.0, 128.0, 128.0);
 
     let mut batch = DrawBatch::new(&display);
     batch.add_color_triangles(&teapot_vertices, &teapot_indices);
-    inferno_logo.add_to_batch(&mut batch);
+    inferno_logo.add_to_batch(&mut batch, &resource_manager);
     batch.create_buffers();
 
     let mut overlay_batch = DrawBatch::new(&display);
@@ -58,17 +57,14 @@ fn main() {
     let mut window = Window::new(&mut resource_manager, &display, 0, 0, 600, 450);
     /*let button = Rc::new(Button::new(&mut resource_manager));
     window.set_child(button);*/
-    let docks = Docks::new(&mut resource_manager);
-    docks.borrow_mut().add_test_docks();
+    let docks = Docks::new(&mut resource_manager).unwrap();
+    docks.borrow_mut().add_test_docks(&resource_manager);
     window.set_child(docks.clone());
-    window.create_buffers();
+    window.create_buffers(&resource_manager);
 
-    let atlas = inferno_logo_texture.atlas.clone();
+    let atlas = resource_manager.resolve_texture(inferno_logo_texture).get_atlas();
     atlas.borrow_mut().upload(&display);
 
-    gui_font.atlas.borrow_mut().upload(&display);
-    font.atlas.borrow_mut().upload(&display);
-
     let mut mouse_x: i32 = 0;
     let mut mouse_y: i32 = 0;
 
@@ -82,9 +78,9 @@ fn main() {
         target.clear_color_and_depth((0.01, 0.01, 0.01, 1.0), 1.0);
 
         batch.draw(&mut target);
-        window.create_buffers();
+        window.create_buffers(&resource_manager);
         window.draw(&mut target);
-        text.add_to_batch(&mut overlay_batch);
+        text.add_to_batch(&mut overlay_batch, &resource_manager);
         overlay_batch.create_buffers();
         overlay_batch.draw(&mut target);
         overlay_batch.clear();
@@ -122,7 +118,7 @@ fn main() {
             event_listener = None;
         }
         if let Some(ref mut event_listener) = event_listener {
-            event_listener.add_to_batch(&mut overlay_batch);
+            event_listener.add_to_batch(&mut overlay_batch, &resource_manager);
         }
         //println!("{} {}", mouse_x, mouse_y);
     }
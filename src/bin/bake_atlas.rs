@@ -0,0 +1,84 @@
+//! Bakes all PNG files below a directory into atlas pages and a manifest that can be loaded with
+//! `ResourceManager::load_baked_atlas`.
+//!
+//! Usage: `bake_atlas <input directory> <output directory> [atlas name]`
+//!
+//! Regions are named by the path of their image relative to the input directory.
+//!
+//! Nine-slice insets are read from an optional `<image>.png.insets` file next to the image which
+//! contains `<left> <right> <top> <bottom>`.
+
+extern crate inferno;
+
+use inferno::resources::AtlasBaker;
+use std::env;
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::path::Path;
+use std::process;
+
+fn read_insets(path: &Path) -> (f32, f32, f32, f32) {
+    let insets_path = format!("{}.insets", path.display());
+    let mut content = String::new();
+    match File::open(&insets_path) {
+        Ok(mut file) => {
+            if let Err(err) = file.read_to_string(&mut content) {
+                println!("couldn't read {}: {}", insets_path, err);
+                return (0.0, 0.0, 0.0, 0.0);
+            }
+        }
+        Err(_) => return (0.0, 0.0, 0.0, 0.0),
+    }
+    let values: Vec<f32> = content.split_whitespace().filter_map(|v| v.parse().ok()).collect();
+    if values.len() != 4 {
+        println!("ignoring {}: expected 4 values", insets_path);
+        return (0.0, 0.0, 0.0, 0.0);
+    }
+    (values[0], values[1], values[2], values[3])
+}
+
+/// Adds the images below `directory`, named by their path relative to `root`.
+fn add_directory(baker: &mut AtlasBaker, root: &Path, directory: &Path) {
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(err) => {
+            println!("couldn't read {}: {}", directory.display(), err);
+            process::exit(1);
+        }
+    };
+    let mut paths: Vec<_> = entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()).collect();
+    paths.sort();
+
+    for path in paths {
+        if path.is_dir() {
+            add_directory(baker, root, &path);
+        } else if path.extension().map_or(false, |extension| extension == "png") {
+            let insets = read_insets(&path);
+            if let Err(err) = baker.add_file(root, &path, insets) {
+                println!("couldn't load {}: {}", path.display(), err);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        println!("usage: {} <input directory> <output directory> [atlas name]", args[0]);
+        process::exit(1);
+    }
+    let name = if args.len() > 3 { &args[3][..] } else { "atlas" };
+
+    let mut baker = AtlasBaker::new();
+    let input = Path::new(&args[1]);
+    add_directory(&mut baker, input, input);
+
+    match baker.bake(Path::new(&args[2]), name) {
+        Ok(baked_atlas) => println!("baked {} regions into {} pages", baked_atlas.regions.len(), baked_atlas.pages.len()),
+        Err(err) => {
+            println!("couldn't bake atlas: {}", err);
+            process::exit(1);
+        }
+    }
+}
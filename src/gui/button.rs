@@ -30,8 +30,8 @@ impl Widget for Button {
         self.button_image.set_size(width as f32, height as f32);
     }
 
-    fn add_to_batch(&self, batch: &mut DrawBatch) {
-        self.button_image.add_to_batch(batch);
+    fn add_to_batch(&self, batch: &mut DrawBatch, resource_manager: &ResourceManager) {
+        self.button_image.add_to_batch(batch, resource_manager);
     }
 
     fn get_highest_priority_child(&self, x: i32, y: i32) -> (i32, Option<Rc<RefCell<Widget>>>) {
@@ -1,5 +1,5 @@
 use super::{Widget, Rectangle, EventListener, BorderImage, Text};
-use ::resources::{ResourceManager, Texture, Font};
+use ::resources::{ResourceManager, ResourceManagerError, TextureHandle, FontHandle};
 use ::rendering::DrawBatch;
 use std::cell::Cell;
 use std::cell::RefCell;
@@ -36,7 +36,7 @@ trait DockCell {
     fn set_position(&mut self, x: i32, y: i32);
     fn get_dimensions(&self) -> (i32, i32);
     fn set_dimensions(&mut self, width: i32, y: i32);
-    fn add_to_batch(&self, batch: &mut DrawBatch);
+    fn add_to_batch(&self, batch: &mut DrawBatch, resource_manager: &ResourceManager);
     fn get_dock_at_position(&self, x: i32, y: i32) -> Option<u32>;
     fn get_dock_group_at_position(&self, x: i32, y: i32) -> Option<u32>;
     fn get_new_dock_cell_position(&self, x: i32, y: i32, vertical_align: bool, index: usize) -> Option<NewDockCellPosition>;
@@ -147,9 +147,9 @@ impl DockCell for DockTable {
         }
     }
 
-    fn add_to_batch(&self, batch: &mut DrawBatch) {
+    fn add_to_batch(&self, batch: &mut DrawBatch, resource_manager: &ResourceManager) {
         for cell in self.dock_cells.iter() {
-            cell.borrow().add_to_batch(batch);
+            cell.borrow().add_to_batch(batch, resource_manager);
         }
     }
 
@@ -230,7 +230,7 @@ pub struct DockGroup {
 }
 
 impl DockGroup {
-    fn new(id: u32, dock_background: Texture) -> DockGroup {
+    fn new(id: u32, dock_background: TextureHandle) -> DockGroup {
         DockGroup {
             id: id,
             parent_id: None,
@@ -299,10 +299,10 @@ impl DockCell for DockGroup {
         self.dock_tab_deselected.set_size(74.0, 21.0);*/
     }
 
-    fn add_to_batch(&self, batch: &mut DrawBatch) {
-        self.dock_background.add_to_batch(batch);
+    fn add_to_batch(&self, batch: &mut DrawBatch, resource_manager: &ResourceManager) {
+        self.dock_background.add_to_batch(batch, resource_manager);
         for d in self.docks.iter() {
-            d.borrow().add_to_batch(batch);
+            d.borrow().add_to_batch(batch, resource_manager);
         }
         /*self.dock_tab_selected.add_to_batch(batch);
         self.dock_tab_deselected.add_to_batch(batch);*/
@@ -408,19 +408,20 @@ pub struct Dock {
 }
 
 impl Dock {
-    fn new(id: u32, font: Font, label: &str, dock_tab_selected: Texture, dock_tab_deselected: Texture) -> Dock {
+    fn new(resource_manager: &ResourceManager, id: u32, font: FontHandle, label: &str,
+           dock_tab_selected: TextureHandle, dock_tab_deselected: TextureHandle) -> Dock {
         let mut result = Dock {
             id: id,
             group_id: None,
             widget: None,
             tab_rect: Rectangle::new(),
             is_selected: true,
-            label: Text::new(font.clone(), label),
+            label: Text::new(resource_manager, font, label),
             dock_tab_selected: BorderImage::new(dock_tab_selected, 5.0, 5.0, 0.0, 0.0),
             dock_tab_deselected: BorderImage::new(dock_tab_deselected, 5.0, 5.0, 0.0, 0.0),
             dont_draw: false,
         };
-        let width = result.label.get_width() as i32 + 16;
+        let width = result.label.get_width(resource_manager) as i32 + 16;
         result.set_tab_dimensions(width, 20);
         result
     }
@@ -431,7 +432,7 @@ impl Dock {
 
     fn set_tab_position(&mut self, x: i32, y: i32) {
         self.tab_rect.position = (x, y);
-        self.label.set_position(x as f32 + 8.0, y as f32 - 15.0);
+        self.label.set_centered_position(x as f32 + 8.0, y as f32, self.tab_rect.dimensions.1 as f32);
         self.dock_tab_selected.set_position(x as f32 - 2.0, y as f32 + 1.0);
         self.dock_tab_deselected.set_position(x as f32 - 2.0, y as f32 + 1.0);
     }
@@ -454,14 +455,14 @@ impl Dock {
         (self.label.clone(), self.dock_tab_selected.clone())
     }
 
-    fn add_to_batch(&self, batch: &mut DrawBatch) {
+    fn add_to_batch(&self, batch: &mut DrawBatch, resource_manager: &ResourceManager) {
         if !self.dont_draw {
             if self.is_selected {
-                self.dock_tab_selected.add_to_batch(batch);
+                self.dock_tab_selected.add_to_batch(batch, resource_manager);
             } else {
-                self.dock_tab_deselected.add_to_batch(batch);
+                self.dock_tab_deselected.add_to_batch(batch, resource_manager);
             }
-            self.label.add_to_batch(batch);
+            self.label.add_to_batch(batch, resource_manager);
         }
     }
 }
@@ -474,6 +475,7 @@ struct MoveDock {
     label: Text,
     border_image: BorderImage,
     border_image_offset: (f32, f32),
+    label_height: f32,
 }
 
 impl MoveDock {
@@ -483,6 +485,7 @@ impl MoveDock {
         dock.borrow_mut().set_dont_draw(true);
         let (label, border_image) = dock.borrow().get_visual_clone();
         let border_image_position = border_image.get_position();
+        let label_height = dock.borrow().tab_rect.dimensions.1 as f32;
         MoveDock {
             root_widget: root_widget,
             dock_id: dock_id,
@@ -491,6 +494,7 @@ impl MoveDock {
             label: label,
             border_image: border_image,
             border_image_offset: (border_image_position.0 - mouse_x as f32, border_image_position.1 + mouse_y as f32),
+            label_height: label_height,
         }
     }
 }
@@ -522,10 +526,12 @@ impl EventListener for MoveDock {
                 if let Some(ref tabs_rect) = self.tabs_rect {
                     self.root_widget.borrow_mut().move_dock_to_position(self.dock_id, x);
                     self.border_image.set_position(x as f32 + self.border_image_offset.0, tabs_rect.position.1 as f32 + 1.0);
-                    self.label.set_position(x as f32 + self.border_image_offset.0 + 10.0, tabs_rect.position.1 as f32 - 15.0);
+                    self.label.set_centered_position(x as f32 + self.border_image_offset.0 + 10.0, tabs_rect.position.1 as f32,
+                                                     self.label_height);
                 } else {
                     self.border_image.set_position(x as f32 + self.border_image_offset.0, -y as f32 + self.border_image_offset.1);
-                    self.label.set_position(x as f32 + self.border_image_offset.0 + 10.0, -y as f32 + self.border_image_offset.1 - 16.0);
+                    self.label.set_centered_position(x as f32 + self.border_image_offset.0 + 10.0, -y as f32 + self.border_image_offset.1 - 1.0,
+                                                     self.label_height);
                 }
                 self.mouse_pos = (x, y);
             }
@@ -546,9 +552,9 @@ impl EventListener for MoveDock {
         false
     }
 
-    fn add_to_batch(&self, batch: &mut DrawBatch) {
-        self.border_image.add_to_batch(batch);
-        self.label.add_to_batch(batch);
+    fn add_to_batch(&self, batch: &mut DrawBatch, resource_manager: &ResourceManager) {
+        self.border_image.add_to_batch(batch, resource_manager);
+        self.label.add_to_batch(batch, resource_manager);
     }
 }
 
@@ -591,14 +597,14 @@ pub struct Docks {
     docks: HashMap<u32, Rc<RefCell<Dock>>>,
     dock_table_root: Option<Rc<RefCell<DockTable>>>,
 
-    font: Font,
-    dock_background: Texture,
-    dock_tab_selected: Texture,
-    dock_tab_deselected: Texture,
+    font: FontHandle,
+    dock_background: TextureHandle,
+    dock_tab_selected: TextureHandle,
+    dock_tab_deselected: TextureHandle,
 }
 
 impl Docks {
-    pub fn new(resource_manager: &mut ResourceManager) -> Rc<RefCell<Docks>> {
+    pub fn new(resource_manager: &mut ResourceManager) -> Result<Rc<RefCell<Docks>>, ResourceManagerError> {
         //let mut index_pool = IndexPool::new();
         //let root_index = index_pool.get_index();
 
@@ -616,17 +622,17 @@ impl Docks {
             /*dock_background: BorderImage::new(bg_texture, 3.0, 3.0, 2.0, 4.0),
             dock_tab_selected: BorderImage::new(selected_texture, 5.0, 5.0, 0.0, 0.0),
             dock_tab_deselected: BorderImage::new(deselected_texture, 5.0, 5.0, 0.0, 0.0),*/
-            font: resource_manager.create_font("DejaVuSans.ttf", 14).unwrap(),
-            dock_background: resource_manager.create_texture("example_images/dock.png").unwrap(),
-            dock_tab_selected: resource_manager.create_texture("example_images/dock_tab_selected.png").unwrap(),
-            dock_tab_deselected: resource_manager.create_texture("example_images/dock_tab_deselected.png").unwrap(),
+            font: try!(resource_manager.create_font("DejaVuSans.ttf", 14)),
+            dock_background: try!(resource_manager.create_texture("example_images/dock.png")),
+            dock_tab_selected: try!(resource_manager.create_texture("example_images/dock_tab_selected.png")),
+            dock_tab_deselected: try!(resource_manager.create_texture("example_images/dock_tab_deselected.png")),
         }));
         //result.borrow_mut().dock_tables.insert(root_index, dock_table_root);
         let weak_self = Some(Rc::downgrade(&result));
         result.borrow_mut().weak_self = weak_self;
         let dock_table_root = result.borrow_mut().create_dock_table(false);
         result.borrow_mut().dock_table_root = Some(dock_table_root);
-        result
+        Ok(result)
     }
 
     pub fn create_dock_table(&mut self, vertical_align: bool) -> Rc<RefCell<DockTable>> {
@@ -638,15 +644,15 @@ impl Docks {
 
     pub fn create_dock_group(&mut self) -> Rc<RefCell<DockGroup>> {
         let result = Rc::new(RefCell::new(
-            DockGroup::new(self.index_pool.get_index(), self.dock_background.clone())
+            DockGroup::new(self.index_pool.get_index(), self.dock_background)
         ));
         self.dock_groups.insert(result.borrow().id, result.clone());
         result
     }
 
-    pub fn create_dock(&mut self, label: &str) -> Rc<RefCell<Dock>> {
+    pub fn create_dock(&mut self, resource_manager: &ResourceManager, label: &str) -> Rc<RefCell<Dock>> {
         let result = Rc::new(RefCell::new(
-            Dock::new(self.index_pool.get_index(), self.font.clone(), label, self.dock_tab_selected.clone(), self.dock_tab_deselected.clone())
+            Dock::new(resource_manager, self.index_pool.get_index(), self.font, label, self.dock_tab_selected, self.dock_tab_deselected)
         ));
         self.docks.insert(result.borrow().id, result.clone());
         result
@@ -901,13 +907,13 @@ impl Docks {
         }
     }
 
-    pub fn add_test_docks(&mut self) {
-        let dock1 = self.create_dock("Tools");
-        let dock2 = self.create_dock("Tools2");
-        let dock3 = self.create_dock("View");
-        let dock4 = self.create_dock("Properties");
-        let dock5 = self.create_dock("Dock5");
-        let dock6 = self.create_dock("Dock6");
+    pub fn add_test_docks(&mut self, resource_manager: &ResourceManager) {
+        let dock1 = self.create_dock(resource_manager, "Tools");
+        let dock2 = self.create_dock(resource_manager, "Tools2");
+        let dock3 = self.create_dock(resource_manager, "View");
+        let dock4 = self.create_dock(resource_manager, "Properties");
+        let dock5 = self.create_dock(resource_manager, "Dock5");
+        let dock6 = self.create_dock(resource_manager, "Dock6");
 
         let d_table_left = self.create_dock_table(true);
         let d1_left = self.create_dock_group();
@@ -954,10 +960,10 @@ impl Widget for Docks {
         //self.button_image.borrow_mut().set_size(width as f32, height as f32);
     }
 
-    fn add_to_batch(&self, batch: &mut DrawBatch) {
+    fn add_to_batch(&self, batch: &mut DrawBatch, resource_manager: &ResourceManager) {
         //self.button_image.borrow().add_to_batch(batch);
         if let Some(ref dock_table_root) = self.dock_table_root {
-            dock_table_root.borrow().add_to_batch(batch);
+            dock_table_root.borrow().add_to_batch(batch, resource_manager);
         }
     }
 
@@ -1,9 +1,9 @@
-use ::resources::{Texture, Font};
-use ::rendering::{DrawBatch, SpriteVertex};
+use ::resources::{ResourceManager, Texture, TextureHandle, FontHandle, Font, Glyph, GlyphMode, SpriteFrame};
+use ::rendering::{DrawBatch, SpriteVertex, DistanceFieldStyle};
 
 #[derive(Clone)]
 pub struct Image {
-    texture: Texture,
+    texture: TextureHandle,
     x: f32,
     y: f32,
     width: f32,
@@ -11,7 +11,7 @@ pub struct Image {
 }
 
 impl Image {
-    pub fn new(texture: Texture, x: f32, y: f32, width: f32, height: f32) -> Self {
+    pub fn new(texture: TextureHandle, x: f32, y: f32, width: f32, height: f32) -> Self {
         Image {
             texture: texture,
             x: x,
@@ -21,40 +21,143 @@ impl Image {
         }
     }
 
-    pub fn add_to_batch(&self, batch: &mut DrawBatch) {
-        let (x, y, width, height) = (self.x, self.y, self.width, self.height);
-        let (u_min, u_max, v_min, v_max) = (self.texture.uv_min.0, self.texture.uv_max.0,
-                                            self.texture.uv_min.1, self.texture.uv_max.1);
-        let vertices = [
-            SpriteVertex {
-                position: [x, y],
-                tex_coords: [u_min, v_min],
-                color: [255, 255, 255, 255],
-            },
-            SpriteVertex {
-                position: [x + width, y],
-                tex_coords: [u_max, v_min],
-                color: [255, 255, 255, 255],
-            },
-            SpriteVertex {
-                position: [x, y - height],
-                tex_coords: [u_min, v_max],
-                color: [255, 255, 255, 255],
-            },
-            SpriteVertex {
-                position: [x + width, y - height],
-                tex_coords: [u_max, v_max],
-                color: [255, 255, 255, 255],
-            },
-        ];
-        let indices: [u32; 6] = [0, 2, 1, 1, 2, 3];
-        batch.add_sprite_triangles(self.texture.atlas.clone(), &vertices, &indices);
+    /// Creates an image at the logical size of `texture`, so HiDPI variants of the texture take
+    /// up the same space as the regular one.
+    pub fn new_with_texture_size(resource_manager: &ResourceManager, texture: TextureHandle, x: f32, y: f32) -> Self {
+        let (width, height) = resource_manager.resolve_texture(texture).get_logical_size();
+        Image::new(texture, x, y, width, height)
+    }
+
+    pub fn add_to_batch(&self, batch: &mut DrawBatch, resource_manager: &ResourceManager) {
+        let texture = resource_manager.resolve_texture(self.texture);
+        add_image_quad(batch, &texture, self.x, self.y, self.width, self.height);
     }
 }
 
+/// Plays the frames of a sprite sheet animation, see `ResourceManager::create_sprite_sheet`.
+#[derive(Clone)]
+pub struct AnimatedImage {
+    frames: Vec<SpriteFrame>,
+    looping: bool,
+    time: f32,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
+
+impl AnimatedImage {
+    pub fn new(frames: &[SpriteFrame], x: f32, y: f32, width: f32, height: f32) -> Self {
+        AnimatedImage {
+            frames: frames.to_vec(),
+            looping: true,
+            time: 0.0,
+            x: x,
+            y: y,
+            width: width,
+            height: height,
+        }
+    }
+
+    /// Animations loop by default, otherwise they stop at the last frame.
+    pub fn set_looping(&mut self, looping: bool) {
+        self.looping = looping;
+    }
+
+    pub fn set_position(&mut self, x: f32, y: f32) {
+        self.x = x;
+        self.y = y;
+    }
+
+    pub fn set_size(&mut self, width: f32, height: f32) {
+        self.width = width;
+        self.height = height;
+    }
+
+    /// Advances the animation by `delta` seconds.
+    pub fn update(&mut self, delta: f32) {
+        self.time += delta;
+        let duration = self.get_duration();
+        if self.looping && duration > 0.0 {
+            self.time %= duration;
+        }
+    }
+
+    /// Starts the animation from the first frame again.
+    pub fn restart(&mut self) {
+        self.time = 0.0;
+    }
+
+    pub fn get_frame_index(&self) -> usize {
+        let mut end = 0.0;
+        for (i, frame) in self.frames.iter().enumerate() {
+            end += frame.duration;
+            if self.time < end {
+                return i;
+            }
+        }
+        if self.frames.is_empty() { 0 } else { self.frames.len() - 1 }
+    }
+
+    /// Length of one pass through all frames in seconds.
+    pub fn get_duration(&self) -> f32 {
+        self.frames.iter().map(|frame| frame.duration).sum()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        !self.looping && self.time >= self.get_duration()
+    }
+
+    pub fn add_to_batch(&self, batch: &mut DrawBatch, resource_manager: &ResourceManager) {
+        if let Some(frame) = self.frames.get(self.get_frame_index()) {
+            if frame.source_size.0 <= 0.0 || frame.source_size.1 <= 0.0 {
+                return;
+            }
+            // the untrimmed sprite fills the image, a trimmed frame only covers its part of it
+            let texture = resource_manager.resolve_texture(frame.texture);
+            let (frame_width, frame_height) = texture.get_logical_size();
+            let (scale_x, scale_y) = (self.width / frame.source_size.0, self.height / frame.source_size.1);
+            add_image_quad(batch, &texture, self.x + frame.offset.0 * scale_x, self.y - frame.offset.1 * scale_y,
+                           frame_width * scale_x, frame_height * scale_y);
+        }
+    }
+}
+
+/// Adds a quad showing `texture` with its flips and rotation.
+fn add_image_quad(batch: &mut DrawBatch, texture: &Texture, x: f32, y: f32, width: f32, height: f32) {
+    let vertices = [
+        SpriteVertex {
+            position: [x, y],
+            tex_coords: uv_array(texture.get_uv(0.0, 0.0)),
+            color: [255, 255, 255, 255],
+        },
+        SpriteVertex {
+            position: [x + width, y],
+            tex_coords: uv_array(texture.get_uv(1.0, 0.0)),
+            color: [255, 255, 255, 255],
+        },
+        SpriteVertex {
+            position: [x, y - height],
+            tex_coords: uv_array(texture.get_uv(0.0, 1.0)),
+            color: [255, 255, 255, 255],
+        },
+        SpriteVertex {
+            position: [x + width, y - height],
+            tex_coords: uv_array(texture.get_uv(1.0, 1.0)),
+            color: [255, 255, 255, 255],
+        },
+    ];
+    let indices: [u32; 6] = [0, 2, 1, 1, 2, 3];
+    batch.add_sprite_triangles(texture.get_atlas(), &vertices, &indices);
+}
+
+fn uv_array(uv: (f32, f32)) -> [f32; 2] {
+    [uv.0, uv.1]
+}
+
 #[derive(Clone)]
 pub struct BorderImage {
-    texture: Texture,
+    texture: TextureHandle,
     x: f32,
     y: f32,
     width: f32,
@@ -66,7 +169,7 @@ pub struct BorderImage {
 }
 
 impl BorderImage {
-    pub fn new(texture: Texture, border_left: f32, border_right: f32,
+    pub fn new(texture: TextureHandle, border_left: f32, border_right: f32,
                border_top: f32, border_bottom: f32) -> Self {
         BorderImage {
             texture: texture,
@@ -80,7 +183,16 @@ impl BorderImage {
             border_bottom: border_bottom,
         }
     }
-    pub fn new_with_values(texture: Texture, x: f32, y: f32, width: f32, height: f32,
+    /// Uses the nine-slice insets of the texture (see `AtlasBaker`) as borders, converted to
+    /// logical units.
+    pub fn new_from_insets(resource_manager: &ResourceManager, texture: TextureHandle) -> Self {
+        let resolved = resource_manager.resolve_texture(texture);
+        let (left, right, top, bottom) = resolved.get_insets();
+        let scale = resolved.get_scale();
+        BorderImage::new(texture, left / scale, right / scale, top / scale, bottom / scale)
+    }
+
+    pub fn new_with_values(texture: TextureHandle, x: f32, y: f32, width: f32, height: f32,
            border_left: f32, border_right: f32, border_top: f32, border_bottom: f32) -> BorderImage {
         BorderImage {
             texture: texture,
@@ -109,178 +221,150 @@ impl BorderImage {
         self.height = height;
     }
 
-    pub fn add_to_batch(&self, batch: &mut DrawBatch) {
+    pub fn add_to_batch(&self, batch: &mut DrawBatch, resource_manager: &ResourceManager) {
+        let texture = resource_manager.resolve_texture(self.texture);
         let (x, y, width, height) = (self.x, self.y, self.width, self.height);
-        let (u_min, u_max, v_min, v_max) = (self.texture.uv_min.0, self.texture.uv_max.0,
-                                            self.texture.uv_min.1, self.texture.uv_max.1);
         let (left, right, top, bottom) = (self.border_left, self.border_right,
                                           self.border_top, self.border_bottom);
-        let pixel_dimension = self.texture.pixel_dimension;
-        let (left_u, right_u, top_v, bottom_v) = (left * pixel_dimension, right * pixel_dimension,
-                                                  top * pixel_dimension, bottom * pixel_dimension);
-        let vertices = [
-            SpriteVertex {
-                position: [x, y],
-                tex_coords: [u_min, v_min],
-                color: [255, 255, 255, 255],
-            },
-            SpriteVertex {
-                position: [x + left, y],
-                tex_coords: [u_min + left_u, v_min],
-                color: [255, 255, 255, 255],
-            },
-            SpriteVertex {
-                position: [x + width - right, y],
-                tex_coords: [u_max - right_u, v_min],
-                color: [255, 255, 255, 255],
-            },
-            SpriteVertex {
-                position: [x + width, y],
-                tex_coords: [u_max, v_min],
-                color: [255, 255, 255, 255],
-            },
-
-            SpriteVertex {
-                position: [x, y - top],
-                tex_coords: [u_min, v_min + top_v],
-                color: [255, 255, 255, 255],
-            },
-            SpriteVertex {
-                position: [x + left, y - top],
-                tex_coords: [u_min + left_u, v_min + top_v],
-                color: [255, 255, 255, 255],
-            },
-            SpriteVertex {
-                position: [x + width - right, y - top],
-                tex_coords: [u_max - right_u, v_min + top_v],
-                color: [255, 255, 255, 255],
-            },
-            SpriteVertex {
-                position: [x + width,y - top],
-                tex_coords: [u_max, v_min + top_v],
-                color: [255, 255, 255, 255],
-            },
-
-            SpriteVertex {
-                position: [x, y - height + bottom],
-                tex_coords: [u_min, v_max - bottom_v],
-                color: [255, 255, 255, 255],
-            },
-            SpriteVertex {
-                position: [x + left, y - height + bottom],
-                tex_coords: [u_min + left_u, v_max - bottom_v],
-                color: [255, 255, 255, 255],
-            },
-            SpriteVertex {
-                position: [x + width - right, y - height + bottom],
-                tex_coords: [u_max - right_u, v_max - bottom_v],
-                color: [255, 255, 255, 255],
-            },
-            SpriteVertex {
-                position: [x + width, y - height + bottom],
-                tex_coords: [u_max, v_max - bottom_v],
-                color: [255, 255, 255, 255],
-            },
-
-            SpriteVertex {
-                position: [x, y - height],
-                tex_coords: [u_min, v_max],
-                color: [255, 255, 255, 255],
-            },
-            SpriteVertex {
-                position: [x + left, y - height],
-                tex_coords: [u_min + left_u, v_max],
-                color: [255, 255, 255, 255],
-            },
-            SpriteVertex {
-                position: [x + width - right, y - height],
-                tex_coords: [u_max - right_u, v_max],
-                color: [255, 255, 255, 255],
-            },
-            SpriteVertex {
-                position: [x + width, y - height],
-                tex_coords: [u_max, v_max],
-                color: [255, 255, 255, 255],
-            },
-        ];
+        // the borders are given in logical units of the texture as it is shown, so flipped and
+        // rotated textures keep their corners in the corners of the image and HiDPI variants
+        // sample their extra pixels
+        let (texture_width, texture_height) = texture.get_logical_size();
+        let xs = [x, x + left, x + width - right, x + width];
+        let ys = [y, y - top, y - height + bottom, y - height];
+        let ss = [0.0, left / texture_width, 1.0 - right / texture_width, 1.0];
+        let ts = [0.0, top / texture_height, 1.0 - bottom / texture_height, 1.0];
+        let mut vertices = Vec::with_capacity(16);
+        for row in 0..4 {
+            for column in 0..4 {
+                vertices.push(SpriteVertex {
+                    position: [xs[column], ys[row]],
+                    tex_coords: uv_array(texture.get_uv(ss[column], ts[row])),
+                    color: [255, 255, 255, 255],
+                });
+            }
+        }
         let indices: [u32; 54] = [0, 4, 1, 1, 4, 5, 1, 5, 2, 2, 5, 6, 2, 6, 3, 3, 6, 7,
                                  4, 8, 5, 5, 8, 9, 5, 9, 6, 6, 9, 10, 6, 10, 7, 7, 10, 11,
                                  8, 12, 9, 9, 12, 13, 9, 13, 10, 10, 13, 14, 10, 14, 11, 11, 14, 15];
-        batch.add_sprite_triangles(self.texture.atlas.clone(), &vertices, &indices);
+        batch.add_sprite_triangles(texture.get_atlas(), &vertices, &indices);
     }
 }
 
+/// A line of text. The glyphs are looked up through the `ResourceManager` every time the text is
+/// added to a batch, text with an unloaded font isn't drawn.
 #[derive(Clone)]
 pub struct Text {
-    font: Font,
+    font: FontHandle,
     text: String,
     x: f32,
     y: f32,
+    /// Height of the box the text is centred in, `y` is the top of the box then.
+    box_height: Option<f32>,
     color: (u8, u8, u8, u8),
-    width: f32,
+    scale: f32,
+    style: DistanceFieldStyle,
     //height: f32,
 }
 
 impl Text {
-    pub fn new(font: Font, text: &str) -> Self {
-        let mut result = Text {
+    pub fn new(resource_manager: &ResourceManager, font: FontHandle, text: &str) -> Self {
+        // glyphs that can't be loaded are left out when the text is drawn
+        if let Some(font) = resource_manager.resolve_font(font) {
+            let _ = font.load_glyphs(text.chars());
+        }
+        Text {
             font: font,
             text: text.to_string(),
             x: 0.0,
             y: 0.0,
+            box_height: None,
             color: (255, 255, 255, 255),
-            width: 0.0,
+            scale: 1.0,
+            style: DistanceFieldStyle::new(),
             //height: 0.0,
-        };
+        }
+    }
+
+    /// Measures the text at scale 1.
+    fn measure(&self, font: &Font) -> f32 {
         let mut width = 0.0;
         let mut last = None;
-        for c in text.chars() {
-            let glyphs = result.font.glyphs.borrow();
-            let glyph = match glyphs.get(&c) {
-                Some(glyph) => glyph,
-                None => continue,
+        for c in self.text.chars() {
+            let glyph = match font.get_glyph(c) {
+                Ok(Some(glyph)) => glyph,
+                _ => continue,
             };
             let mut kerning: f32 = 0.0;
-            if let Some(last) = last {
-                kerning = *result.font.kernings.borrow().get(&(last, c)).unwrap_or(&0.0);
+            if let Some((last_c, ref last_glyph)) = last {
+                kerning = font.get_kerning((last_c, last_glyph), (c, &glyph));
             }
-            last = Some(c);
             width += glyph.advance_x + kerning;
+            last = Some((c, glyph));
         }
-        result.width = width;
-        result
+        width
     }
 
+    /// Draws the text at `scale` times the font size. This only looks good for fonts that were
+    /// created with `GlyphMode::DistanceField`.
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale;
+    }
+
+    /// Edge softness, outline and glow of text with a distance field font, ignored otherwise.
+    pub fn set_distance_field_style(&mut self, style: DistanceFieldStyle) {
+        self.style = style;
+    }
+
+    /// Places the baseline of the text at `y`.
     pub fn set_position(&mut self, x: f32, y: f32) {
         self.x = x;
         self.y = y;
+        self.box_height = None;
+    }
+
+    /// Centres the text vertically in a box of `height` whose top is at `top`. The baseline is
+    /// taken from the metrics of the font every time the text is added to a batch, so it
+    /// follows fonts that are reloaded.
+    pub fn set_centered_position(&mut self, x: f32, top: f32, height: f32) {
+        self.x = x;
+        self.y = top;
+        self.box_height = Some(height);
     }
 
     pub fn set_color(&mut self, r: u8, g: u8, b: u8, a: u8) {
         self.color = (r, g, b, a);
     }
 
-    pub fn get_width(&self) -> f32 {
-        self.width
+    /// Width with the current glyphs of the font, 0 if the font was unloaded.
+    pub fn get_width(&self, resource_manager: &ResourceManager) -> f32 {
+        resource_manager.resolve_font(self.font).map_or(0.0, |font| self.measure(&font) * self.scale)
     }
 
-    fn add_letter(&self, letter: char, x: f32, shadow: bool, batch: &mut DrawBatch) {
-        let glyphs = self.font.glyphs.borrow();
-        let glyph = match glyphs.get(&letter) {
-            Some(glyph) => glyph,
-            None => return,
-        };
+    /// Height from the ascent to the descent of the font, 0 if the font was unloaded.
+    pub fn get_height(&self, resource_manager: &ResourceManager) -> f32 {
+        resource_manager.resolve_font(self.font).map_or(0.0, |font| font.metrics.get_text_height(&self.text) * self.scale)
+    }
 
+    /// Distance from the top of a box of `height` down to the baseline, see also
+    /// `set_centered_position`.
+    pub fn get_baseline_offset(&self, resource_manager: &ResourceManager, height: f32) -> f32 {
+        resource_manager.resolve_font(self.font).map_or(0.0, |font| {
+            font.metrics.get_centered_baseline(height / self.scale) * self.scale
+        })
+    }
+
+    fn add_letter(&self, font: &Font, glyph: &Glyph, x: f32, y: f32, shadow: bool, batch: &mut DrawBatch) {
         let (r, g, b, a) = match shadow {
             true => (0, 0, 0, 255),
             false => (self.color.0, self.color.1, self.color.2, self.color.3),
         };
         let (u_min, u_max, v_min, v_max) = (glyph.uv_min.0, glyph.uv_max.0,
                                             glyph.uv_min.1, glyph.uv_max.1);
-        let (width, height) = (glyph.width, glyph.height);
-        let (offset_x, offset_y) = glyph.offset;
+        let (width, height) = (glyph.width * self.scale, glyph.height * self.scale);
+        let (offset_x, offset_y) = (glyph.offset.0 * self.scale, glyph.offset.1 * self.scale);
         let x = x + offset_x;
-        let mut y = self.y + offset_y;
+        let mut y = y + offset_y;
 
         if shadow {
             y -= 1.0;
@@ -310,20 +394,36 @@ impl Text {
                 },
             ];
             let indices: [u32; 6] = [0, 2, 1, 1, 2, 3];
-            batch.add_font_triangles(self.font.atlas.clone(), &vertices, &indices);
+            // the page decides the shader, glyphs from fallbacks of a bitmap font are coverage
+            let (atlas, mode) = font.get_page(glyph.page);
+            let style = match mode {
+                GlyphMode::DistanceField { .. } => Some(self.style),
+                GlyphMode::Coverage | GlyphMode::Bitmap => None,
+            };
+            batch.add_font_triangles(atlas, style, &vertices, &indices);
         }
     }
 
-    pub fn add_to_batch(&self, batch: &mut DrawBatch) {
+    /// Glyphs can be spread over several glyph cache pages. They are emitted page by page, so
+    /// the text only needs one font batch per page instead of one for every page change.
+    pub fn add_to_batch(&self, batch: &mut DrawBatch, resource_manager: &ResourceManager) {
+        let font = match resource_manager.resolve_font(self.font) {
+            Some(font) => font,
+            None => return,
+        };
         let mut x = self.x;
-        //let mut y;
+        let y = match self.box_height {
+            Some(height) => self.y - font.metrics.get_centered_baseline(height / self.scale) * self.scale,
+            None => self.y,
+        };
 
+        let mut letters = Vec::new();
+        let mut pages = Vec::new();
         let mut last = None;
         for c in self.text.chars() {
-            let glyphs = self.font.glyphs.borrow();
-            let glyph = match glyphs.get(&c) {
-                Some(glyph) => glyph,
-                None => continue,
+            let glyph = match font.get_glyph(c) {
+                Ok(Some(glyph)) => glyph,
+                _ => continue,
             };
 
             /*let (r, g, b, a) = (self.color.0, self.color.1, self.color.2, self.color.3);
@@ -333,14 +433,16 @@ impl Text {
             let (offset_x, offset_y) = glyph.offset;*/
 
             let mut kerning: f32 = 0.0;
-            if let Some(last) = last {
-                kerning = *self.font.kernings.borrow().get(&(last, c)).unwrap_or(&0.0);
+            if let Some((last_c, ref last_glyph)) = last {
+                kerning = font.get_kerning((last_c, last_glyph), (c, &glyph));
             }
-            x += kerning;
+            x += kerning * self.scale;
             //y = self.y + offset_y;
 
-            self.add_letter(c, x, true, batch);
-            self.add_letter(c, x, false, batch);
+            letters.push((glyph, x));
+            if glyph.width != 0.0 && !pages.contains(&glyph.page) {
+                pages.push(glyph.page);
+            }
 
             /*if width != 0.0 {
                 let x = x + offset_x;
@@ -370,8 +472,17 @@ impl Text {
                 batch.add_font_triangles(self.font.atlas.clone(), &vertices, &indices);
             }*/
 
-            last = Some(c);
-            x += glyph.advance_x;
+            x += glyph.advance_x * self.scale;
+            last = Some((c, glyph));
+        }
+
+        // all shadows go below the text
+        for &shadow in &[true, false] {
+            for page in &pages {
+                for &(ref glyph, x) in letters.iter().filter(|&&(ref glyph, _)| glyph.page == *page) {
+                    self.add_letter(&font, glyph, x, y, shadow, batch);
+                }
+            }
         }
     }
 }
@@ -1,4 +1,4 @@
-pub use self::gui_renderer::{Image, BorderImage, Text};
+pub use self::gui_renderer::{Image, AnimatedImage, BorderImage, Text};
 pub use self::window::Window;
 pub use self::widget::{Widget, Rectangle, EventListener};
 pub use self::button::Button;
@@ -2,13 +2,14 @@
 use std::rc::Rc;
 use std::cell::RefCell;
 use ::rendering::DrawBatch;
+use ::resources::ResourceManager;
 use glium::glutin::Event;
 
 pub trait Widget {
     //fn draw(&self, frame: &mut glium::Frame);
     fn set_position(&mut self, x: i32, y: i32);
     fn set_dimensions(&mut self, width: i32, height: i32);
-    fn add_to_batch(&self, batch: &mut DrawBatch);
+    fn add_to_batch(&self, batch: &mut DrawBatch, resource_manager: &ResourceManager);
     fn get_highest_priority_child(&self, x: i32, y: i32) -> (i32, Option<Rc<RefCell<Widget>>>);
     fn create_event_listener(&self, x: i32, y: i32) -> Option<Box<EventListener>> { None }
 }
@@ -42,5 +43,5 @@ impl Rectangle {
 
 pub trait EventListener {
     fn handle_event(&mut self, event: Event) -> bool;
-    fn add_to_batch(&self, batch: &mut DrawBatch) {}
+    fn add_to_batch(&self, batch: &mut DrawBatch, resource_manager: &ResourceManager) {}
 }
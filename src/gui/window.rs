@@ -41,13 +41,13 @@ impl<'a> Window<'a> {
         self.child = Some(child);
     }
 
-    pub fn create_buffers(&mut self) {
+    pub fn create_buffers(&mut self, resource_manager: &ResourceManager) {
         self.draw_batch.clear();
-        self.window_background.add_to_batch(&mut self.draw_batch);
+        self.window_background.add_to_batch(&mut self.draw_batch, resource_manager);
         if let Some(ref child) = self.child {
             child.borrow_mut().set_dimensions(self.rect.dimensions.0 - 2 * PADDING, self.rect.dimensions.1 - 2 * PADDING);
             child.borrow_mut().set_position(self.rect.position.0 + PADDING, self.rect.position.1 - PADDING);
-            child.borrow_mut().add_to_batch(&mut self.draw_batch);
+            child.borrow_mut().add_to_batch(&mut self.draw_batch, resource_manager);
         }
         self.draw_batch.create_buffers();
     }
@@ -2,6 +2,8 @@
 extern crate glium;
 extern crate image;
 extern crate rusttype;
+extern crate zip;
+extern crate rustc_serialize;
 
 pub mod resources;
 pub mod rendering;
@@ -1,5 +1,5 @@
 use glium;
-use super::{PolygonMode, Color2dBatch, ColorTriangleBatch, SpriteTriangleBatch, FontTriangleBatch};
+use super::{PolygonMode, Color2dBatch, ColorTriangleBatch, SpriteTriangleBatch, FontTriangleBatch, DistanceFieldStyle};
 use super::vertex::{ColorVertex2d, ColorVertex, SpriteVertex};
 use ::resources::TextureAtlas;
 use std::rc::Rc;
@@ -91,16 +91,17 @@ impl<'a> DrawBatch<'a> {
         self.batches.push(BatchType::SpriteTriangle(batch));
     }
 
-    pub fn add_font_triangles(&mut self, atlas: Rc<RefCell<TextureAtlas>>, vertices: &[SpriteVertex], indices: &[u32]) {
+    /// `style` has to be `Some` for atlases with distance field glyphs.
+    pub fn add_font_triangles(&mut self, atlas: Rc<RefCell<TextureAtlas>>, style: Option<DistanceFieldStyle>, vertices: &[SpriteVertex], indices: &[u32]) {
         if let Some(&mut BatchType::FontTriangle(ref mut batch)) = self.batches.last_mut() {
             let p1 = &(*atlas) as *const RefCell<TextureAtlas>;
             let p2 = &(*batch.atlas) as *const RefCell<TextureAtlas>;
-            if p1 == p2 {
+            if p1 == p2 && style == batch.style {
                 batch.add_font_triangles(vertices, indices);
                 return;
             }
         }
-        let mut batch = FontTriangleBatch::new(self.display, atlas);
+        let mut batch = FontTriangleBatch::new(self.display, atlas, style);
         batch.add_font_triangles(vertices, indices);
         self.batches.push(BatchType::FontTriangle(batch));
     }
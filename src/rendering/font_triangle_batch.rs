@@ -4,9 +4,39 @@ use ::resources::TextureAtlas;
 use std::rc::Rc;
 use std::cell::RefCell;
 
+/// Parameters of the distance field text shader. Widths are given in distance field units, 0.5
+/// is the full spread of the font.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DistanceFieldStyle {
+    /// Width of the antialiased edge relative to a screen pixel, 1.0 is crisp, larger values blur.
+    pub softness: f32,
+    pub outline_width: f32,
+    pub outline_color: (u8, u8, u8, u8),
+    pub glow_width: f32,
+    pub glow_color: (u8, u8, u8, u8),
+}
+
+impl DistanceFieldStyle {
+    pub fn new() -> Self {
+        DistanceFieldStyle {
+            softness: 1.0,
+            outline_width: 0.0,
+            outline_color: (0, 0, 0, 255),
+            glow_width: 0.0,
+            glow_color: (0, 0, 0, 255),
+        }
+    }
+}
+
+fn to_vec4(color: (u8, u8, u8, u8)) -> [f32; 4] {
+    [color.0 as f32 / 255.0, color.1 as f32 / 255.0, color.2 as f32 / 255.0, color.3 as f32 / 255.0]
+}
+
 pub struct FontTriangleBatch<'a> {
     display: &'a glium::backend::glutin_backend::GlutinFacade,
     pub atlas: Rc<RefCell<TextureAtlas>>,
+    /// `Some` if the atlas contains distance fields instead of coverage.
+    pub style: Option<DistanceFieldStyle>,
     program: glium::Program,
     vertices: Vec<SpriteVertex>,
     indices: Vec<u32>,
@@ -15,7 +45,7 @@ pub struct FontTriangleBatch<'a> {
 }
 
 impl<'a> FontTriangleBatch<'a> {
-    pub fn new(display: &'a glium::backend::glutin_backend::GlutinFacade, atlas: Rc<RefCell<TextureAtlas>>) -> Self {
+    pub fn new(display: &'a glium::backend::glutin_backend::GlutinFacade, atlas: Rc<RefCell<TextureAtlas>>, style: Option<DistanceFieldStyle>) -> Self {
         let vertex_shader_src = r#"
             #version 150
 
@@ -52,9 +82,46 @@ impl<'a> FontTriangleBatch<'a> {
             }
         "#;
 
+        let distance_field_fragment_shader_src = r#"
+            #version 150
+
+            in vec2 v_tex_coords;
+            in vec4 v_color;
+
+            out vec4 color;
+
+            uniform sampler2D tex;
+            uniform float softness;
+            uniform float outline_width;
+            uniform vec4 outline_color;
+            uniform float glow_width;
+            uniform vec4 glow_color;
+
+            void main() {
+                vec4 fill_color = v_color * vec4(0.00392156862, 0.00392156862, 0.00392156862, 0.00392156862);
+                float dist = texture(tex, v_tex_coords).r;
+                float edge = max(fwidth(dist) * softness, 0.0001);
+
+                float fill = smoothstep(0.5 - edge, 0.5 + edge, dist);
+                float outline_edge = 0.5 - outline_width;
+                float outline = smoothstep(outline_edge - edge, outline_edge + edge, dist);
+                float glow = glow_width > 0.0 ? smoothstep(outline_edge - glow_width, outline_edge, dist) : 0.0;
+
+                color = vec4(glow_color.rgb, glow_color.a * glow);
+                color = mix(color, outline_color, outline);
+                color = mix(color, fill_color, fill);
+            }
+        "#;
+
+        let fragment_shader_src = match style {
+            Some(_) => distance_field_fragment_shader_src,
+            None => fragment_shader_src,
+        };
+
         FontTriangleBatch {
             display: display,
             atlas: atlas,
+            style: style,
             //program: glium::Program::from_source(display, vertex_shader_src, fragment_shader_src, None).unwrap(),
             program: glium::Program::new(display, glium::program::ProgramCreationInput:: SourceCode {
                 vertex_shader: vertex_shader_src,
@@ -85,6 +152,7 @@ impl<'a> FontTriangleBatch<'a> {
     }
 
     pub fn create_buffers(&mut self) {
+        self.atlas.borrow_mut().upload(self.display);
         self.vertex_buffer = Some(glium::VertexBuffer::new(self.display, &self.vertices).unwrap());
         self.index_buffer = Some(glium::IndexBuffer::new(self.display, glium::index::PrimitiveType::TrianglesList, &self.indices).unwrap());
     }
@@ -140,12 +208,33 @@ impl<'a> FontTriangleBatch<'a> {
         let atlas = self.atlas.borrow();
         let tex = atlas.get_texture().unwrap();
 
-        frame.draw(vertex_buffer, index_buffer, &self.program,
-                   &uniform! {
-                       projection: projection,
-                       matrix: matrix,
-                       tex: tex.sampled().magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest)
-                   },
-                   &params).unwrap();
+        match self.style {
+            Some(style) => {
+                // distance fields have to be interpolated, that's what keeps them sharp
+                frame.draw(vertex_buffer, index_buffer, &self.program,
+                           &uniform! {
+                               projection: projection,
+                               matrix: matrix,
+                               tex: tex.sampled()
+                                   .magnify_filter(glium::uniforms::MagnifySamplerFilter::Linear)
+                                   .minify_filter(glium::uniforms::MinifySamplerFilter::Linear),
+                               softness: style.softness,
+                               outline_width: style.outline_width,
+                               outline_color: to_vec4(style.outline_color),
+                               glow_width: style.glow_width,
+                               glow_color: to_vec4(style.glow_color),
+                           },
+                           &params).unwrap();
+            }
+            None => {
+                frame.draw(vertex_buffer, index_buffer, &self.program,
+                           &uniform! {
+                               projection: projection,
+                               matrix: matrix,
+                               tex: tex.sampled().magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest)
+                           },
+                           &params).unwrap();
+            }
+        }
     }
 }
@@ -2,7 +2,7 @@ pub use self::vertex::{ColorVertex2d, ColorVertex, SpriteVertex};
 pub use self::color_2d_batch::{PolygonMode, Color2dBatch};
 pub use self::color_triangle_batch::ColorTriangleBatch;
 pub use self::sprite_triangle_batch::SpriteTriangleBatch;
-pub use self::font_triangle_batch::FontTriangleBatch;
+pub use self::font_triangle_batch::{FontTriangleBatch, DistanceFieldStyle};
 pub use self::draw_batch::DrawBatch;
 
 mod vertex;
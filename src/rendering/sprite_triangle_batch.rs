@@ -84,6 +84,7 @@ impl<'a> SpriteTriangleBatch<'a> {
     }
 
     pub fn create_buffers(&mut self) {
+        self.atlas.borrow_mut().upload(self.display);
         self.vertex_buffer = Some(glium::VertexBuffer::new(self.display, &self.vertices).unwrap());
         self.index_buffer = Some(glium::IndexBuffer::new(self.display, glium::index::PrimitiveType::TrianglesList, &self.indices).unwrap());
     }
@@ -142,11 +143,20 @@ impl<'a> SpriteTriangleBatch<'a> {
             None => return,
         };
 
+        let sampler = tex.sampled().magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest);
+        // the texture is created with only the mip levels that fit into the gutters of the atlas,
+        // so sampling can't reach a level where regions bleed into each other
+        let sampler = if atlas.has_mipmaps() {
+            sampler.minify_filter(glium::uniforms::MinifySamplerFilter::LinearMipmapLinear)
+        } else {
+            sampler
+        };
+
         frame.draw(vertex_buffer, index_buffer, &self.program,
                    &uniform! {
                        projection: projection,
                        matrix: matrix,
-                       tex: tex.sampled().magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest)
+                       tex: sampler
                    },
                    &params).unwrap();
     }
@@ -0,0 +1,188 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io;
+use std::io::prelude::*;
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+use zip;
+
+/// Somewhere the `ResourceManager` can read asset files from. Names always use `/` as separator.
+pub trait AssetSource {
+    /// Returns `None` if the source doesn't contain an asset called `name`.
+    fn read(&self, name: &str) -> Option<io::Result<Vec<u8>>>;
+
+    fn contains(&self, name: &str) -> bool;
+
+    /// Modification time of the asset, used for hot-reloading. Sources that can't change return
+    /// `None`.
+    fn get_modified(&self, _name: &str) -> Option<SystemTime> {
+        None
+    }
+}
+
+/// Reads assets from files below a root directory. Names that are absolute or contain `..` are
+/// never found, so assets can't escape the root.
+pub struct DirectorySource {
+    root: PathBuf,
+}
+
+impl DirectorySource {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        DirectorySource {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Returns the file behind `name`, or `None` if the name would leave the root directory.
+    pub fn get_path(&self, name: &str) -> Option<PathBuf> {
+        let is_inside = Path::new(name).components().all(|component| {
+            match component {
+                Component::Normal(_) | Component::CurDir => true,
+                _ => false,
+            }
+        });
+        if is_inside { Some(self.root.join(name)) } else { None }
+    }
+}
+
+impl AssetSource for DirectorySource {
+    fn read(&self, name: &str) -> Option<io::Result<Vec<u8>>> {
+        let path = match self.get_path(name) {
+            Some(ref path) if path.is_file() => path.clone(),
+            _ => return None,
+        };
+        let mut data = Vec::new();
+        let result = File::open(&path).and_then(|mut file| file.read_to_end(&mut data));
+        Some(result.map(|_| data))
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.get_path(name).map_or(false, |path| path.is_file())
+    }
+
+    fn get_modified(&self, name: &str) -> Option<SystemTime> {
+        self.get_path(name).and_then(|path| fs::metadata(path).and_then(|metadata| metadata.modified()).ok())
+    }
+}
+
+/// Reads assets from a zip archive, e.g. a theme or mod package.
+pub struct ZipSource {
+    archive: RefCell<zip::ZipArchive<File>>,
+}
+
+impl ZipSource {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = try!(File::open(path));
+        let archive = try!(zip::ZipArchive::new(file).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)));
+        Ok(ZipSource {
+            archive: RefCell::new(archive),
+        })
+    }
+}
+
+impl AssetSource for ZipSource {
+    fn read(&self, name: &str) -> Option<io::Result<Vec<u8>>> {
+        let mut archive = self.archive.borrow_mut();
+        let mut file = match archive.by_name(name) {
+            Ok(file) => file,
+            Err(zip::result::ZipError::FileNotFound) => return None,
+            Err(err) => return Some(Err(io::Error::new(io::ErrorKind::InvalidData, err))),
+        };
+        let mut data = Vec::new();
+        Some(file.read_to_end(&mut data).map(|_| data))
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.archive.borrow_mut().by_name(name).is_ok()
+    }
+}
+
+/// Keeps assets in memory, for assets generated at runtime or built in with `include_bytes!`.
+pub struct MemorySource {
+    assets: HashMap<String, Vec<u8>>,
+}
+
+impl MemorySource {
+    pub fn new() -> Self {
+        MemorySource {
+            assets: HashMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, name: &str, data: Vec<u8>) {
+        self.assets.insert(name.to_string(), data);
+    }
+}
+
+impl AssetSource for MemorySource {
+    fn read(&self, name: &str) -> Option<io::Result<Vec<u8>>> {
+        self.assets.get(name).map(|data| Ok(data.clone()))
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.assets.contains_key(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use super::{AssetSource, DirectorySource, MemorySource};
+    use resources::{ResourceManager, ResourceManagerError};
+
+    const DOCK: &'static [u8] = include_bytes!("../../example_images/dock.png");
+    const BUTTON_SHADOW: &'static [u8] = include_bytes!("../../example_images/button_shadow.png");
+
+    fn memory_source(name: &str, data: &[u8]) -> Box<AssetSource> {
+        let mut source = MemorySource::new();
+        source.insert(name, data.to_vec());
+        Box::new(source)
+    }
+
+    #[test]
+    fn later_source_overrides_earlier_one() {
+        let mut resource_manager = ResourceManager::new();
+        resource_manager.clear_asset_sources();
+        resource_manager.add_asset_source(memory_source("images/button.png", DOCK));
+        resource_manager.add_asset_source(memory_source("images/button.png", BUTTON_SHADOW));
+        let texture = resource_manager.create_texture("images/button.png").unwrap();
+        assert_eq!(resource_manager.resolve_texture(texture).get_size(), (29, 30));
+    }
+
+    #[test]
+    fn missing_asset_is_an_error() {
+        let source = MemorySource::new();
+        assert!(!source.contains("missing.png"));
+        assert!(source.read("missing.png").is_none());
+
+        let mut resource_manager = ResourceManager::new();
+        resource_manager.clear_asset_sources();
+        resource_manager.add_asset_source(memory_source("dock.png", DOCK));
+        match resource_manager.create_texture("missing.png") {
+            Err(ResourceManagerError::Io(ref err)) => assert_eq!(err.kind(), io::ErrorKind::NotFound),
+            _ => panic!("missing.png was found"),
+        }
+    }
+
+    #[test]
+    fn texture_from_memory_is_found_by_name() {
+        let mut resource_manager = ResourceManager::new();
+        resource_manager.clear_asset_sources();
+        let texture = resource_manager.create_texture_from_memory("dock.png", DOCK).unwrap();
+        assert_eq!(resource_manager.get_texture("dock.png"), Some(texture));
+        // already registered, so no source is asked for the file
+        assert_eq!(resource_manager.create_texture("dock.png").unwrap(), texture);
+        assert_eq!(resource_manager.resolve_texture(texture).get_size(), (11, 11));
+    }
+
+    #[test]
+    fn directory_source_stays_inside_root() {
+        let source = DirectorySource::new("example_images");
+        assert!(source.contains("dock.png"));
+        assert!(source.contains("./dock.png"));
+        assert!(!source.contains("../example_images/dock.png"));
+        assert!(!source.contains("/etc/hostname"));
+        assert!(source.read("../Cargo.toml").is_none());
+    }
+}
@@ -0,0 +1,65 @@
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, Sender, Receiver};
+use std::thread;
+use std::panic;
+use std::io;
+use image;
+use super::resource_manager::{Result, ResourceManagerError};
+
+/// Decodes images on a pool of worker threads. Jobs and results are identified by the id of the
+/// texture name, the results have to be collected on the thread that owns the atlases.
+pub struct AsyncLoader {
+    job_sender: Sender<(u64, Vec<u8>)>,
+    result_receiver: Receiver<(u64, image::ImageResult<image::DynamicImage>)>,
+}
+
+impl AsyncLoader {
+    pub fn new(worker_count: usize) -> Self {
+        let (job_sender, job_receiver) = channel::<(u64, Vec<u8>)>();
+        let (result_sender, result_receiver) = channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        for _ in 0..worker_count {
+            let job_receiver = job_receiver.clone();
+            let result_sender = result_sender.clone();
+            thread::spawn(move || {
+                loop {
+                    // the workers exit once the loader and with it the job sender is dropped, the
+                    // receiver stays usable even if another worker panicked while holding the lock
+                    let job = match job_receiver.lock() {
+                        Ok(job_receiver) => job_receiver.recv(),
+                        Err(poisoned) => poisoned.into_inner().recv(),
+                    };
+                    let (id, data) = match job {
+                        Ok(job) => job,
+                        Err(_) => break,
+                    };
+                    // a decoder that panics on a broken file fails that file instead of the worker
+                    let result = match panic::catch_unwind(|| image::load_from_memory(&data)) {
+                        Ok(result) => result,
+                        Err(_) => Err(image::ImageError::FormatError("the decoder panicked".to_string())),
+                    };
+                    if result_sender.send((id, result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+
+        AsyncLoader {
+            job_sender: job_sender,
+            result_receiver: result_receiver,
+        }
+    }
+
+    /// Queues `data` for decoding, fails if all workers exited.
+    pub fn decode(&self, id: u64, data: Vec<u8>) -> Result<()> {
+        self.job_sender.send((id, data)).map_err(|_| {
+            ResourceManagerError::Io(io::Error::new(io::ErrorKind::BrokenPipe, "all image decoding workers exited"))
+        })
+    }
+
+    pub fn try_recv(&self) -> Option<(u64, image::ImageResult<image::DynamicImage>)> {
+        self.result_receiver.try_recv().ok()
+    }
+}
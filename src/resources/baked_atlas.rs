@@ -0,0 +1,267 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::{self, BufReader};
+use std::path::{Component, Path};
+use image;
+use image::GenericImage;
+use super::texture_atlas::TextureAtlas;
+use super::resource_manager::{Result, ResourceManagerError};
+
+const DEFAULT_PAGE_DIMENSION: usize = 2048;
+const DEFAULT_GUTTER: usize = 2;
+
+/// A named region of a baked atlas page as stored in the manifest.
+#[derive(Debug, Clone)]
+pub struct BakedRegion {
+    pub name: String,
+    pub page: usize,
+    pub position: (usize, usize),
+    pub size: (u32, u32),
+    pub uv_min: (f32, f32),
+    pub uv_max: (f32, f32),
+    pub insets: (f32, f32, f32, f32),
+}
+
+/// Manifest of a baked atlas. The manifest is a text file with one entry per line:
+///
+/// ```text
+/// page <width> <height> <file>
+/// region <page> <x> <y> <width> <height> <u_min> <v_min> <u_max> <v_max> <left> <right> <top> <bottom> <name>
+/// ```
+///
+/// Page files are relative to the manifest, region names may contain spaces.
+pub struct BakedAtlas {
+    pub pages: Vec<(usize, usize, String)>,
+    pub regions: Vec<BakedRegion>,
+}
+
+impl BakedAtlas {
+    pub fn load(path: &Path) -> Result<BakedAtlas> {
+        let file = try!(File::open(path));
+        BakedAtlas::from_reader(file, &path.to_string_lossy())
+    }
+
+    /// Parses a manifest, `name` is only used for error messages.
+    pub fn from_reader<R: Read>(reader: R, name: &str) -> Result<BakedAtlas> {
+        let mut result = BakedAtlas {
+            pages: Vec::new(),
+            regions: Vec::new(),
+        };
+
+        for (i, line) in BufReader::new(reader).lines().enumerate() {
+            let line = try!(line);
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || ResourceManagerError::InvalidManifest(format!("{}: invalid line {}", name, i + 1));
+
+            if line.starts_with("page ") {
+                let fields: Vec<&str> = line.splitn(4, ' ').collect();
+                if fields.len() != 4 {
+                    return Err(invalid());
+                }
+                let width = try!(fields[1].parse::<usize>().map_err(|_| invalid()));
+                let height = try!(fields[2].parse::<usize>().map_err(|_| invalid()));
+                result.pages.push((width, height, fields[3].to_string()));
+            } else if line.starts_with("region ") {
+                let fields: Vec<&str> = line.splitn(15, ' ').collect();
+                if fields.len() != 15 {
+                    return Err(invalid());
+                }
+                let mut integers = Vec::new();
+                for field in &fields[1..6] {
+                    integers.push(try!(field.parse::<u32>().map_err(|_| invalid())));
+                }
+                let mut numbers = Vec::new();
+                for field in &fields[6..14] {
+                    numbers.push(try!(field.parse::<f32>().map_err(|_| invalid())));
+                }
+                let page = integers[0] as usize;
+                if page >= result.pages.len() {
+                    return Err(invalid());
+                }
+                // regions have to lie inside of their page
+                let (page_width, page_height, _) = result.pages[page];
+                let (x, y, width, height) = (integers[1], integers[2], integers[3], integers[4]);
+                let right = x.checked_add(width).map_or(false, |right| right as usize <= page_width);
+                let bottom = y.checked_add(height).map_or(false, |bottom| bottom as usize <= page_height);
+                if !right || !bottom {
+                    return Err(invalid());
+                }
+                result.regions.push(BakedRegion {
+                    name: fields[14].to_string(),
+                    page: page,
+                    position: (x as usize, y as usize),
+                    size: (width, height),
+                    uv_min: (numbers[0], numbers[1]),
+                    uv_max: (numbers[2], numbers[3]),
+                    insets: (numbers[4], numbers[5], numbers[6], numbers[7]),
+                });
+            } else {
+                return Err(invalid());
+            }
+        }
+        Ok(result)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut file = try!(File::create(path));
+        for &(width, height, ref page) in &self.pages {
+            try!(writeln!(file, "page {} {} {}", width, height, page));
+        }
+        for region in &self.regions {
+            try!(writeln!(file, "region {} {} {} {} {} {} {} {} {} {} {} {} {} {}",
+                          region.page, region.position.0, region.position.1, region.size.0, region.size.1,
+                          region.uv_min.0, region.uv_min.1, region.uv_max.0, region.uv_max.1,
+                          region.insets.0, region.insets.1, region.insets.2, region.insets.3,
+                          region.name));
+        }
+        Ok(())
+    }
+}
+
+/// Name of `path` relative to `base`, with the components joined by `/` and `.` left out.
+fn get_relative_name(base: &Path, path: &Path) -> Result<String> {
+    let outside = || {
+        let message = format!("{} is not inside of {}", path.display(), base.display());
+        ResourceManagerError::Io(io::Error::new(io::ErrorKind::InvalidInput, message))
+    };
+    let base: Vec<Component> = base.components().filter(|component| *component != Component::CurDir).collect();
+    let mut components = path.components().filter(|component| *component != Component::CurDir);
+    for base_component in base {
+        if components.next() != Some(base_component) {
+            return Err(outside());
+        }
+    }
+    let mut parts = Vec::new();
+    for component in components {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy().into_owned()),
+            _ => return Err(outside()),
+        }
+    }
+    if parts.is_empty() {
+        return Err(outside());
+    }
+    Ok(parts.join("/"))
+}
+
+/// Packs images into atlas pages with the skyline packer of `TextureAtlas` and writes the pages
+/// together with a `BakedAtlas` manifest.
+pub struct AtlasBaker {
+    dimension: usize,
+    gutter: usize,
+    images: Vec<(String, image::DynamicImage, (f32, f32, f32, f32))>,
+}
+
+impl AtlasBaker {
+    pub fn new() -> Self {
+        AtlasBaker {
+            dimension: DEFAULT_PAGE_DIMENSION,
+            gutter: DEFAULT_GUTTER,
+            images: Vec::new(),
+        }
+    }
+
+    pub fn set_page_dimension(&mut self, dimension: usize) {
+        self.dimension = dimension;
+    }
+
+    pub fn set_gutter(&mut self, gutter: usize) {
+        self.gutter = gutter;
+    }
+
+    /// Adds an image under `name`, which is the name `ResourceManager::get_texture` resolves.
+    /// `insets` are the nine-slice borders in pixels (left, right, top, bottom).
+    pub fn add_image(&mut self, name: &str, img: image::DynamicImage, insets: (f32, f32, f32, f32)) {
+        self.images.push((name.to_string(), img, insets));
+    }
+
+    /// Adds the image file at `path`, which has to be below `base`. The image is named by its
+    /// path relative to `base` with `/` as separator, so `base` should be the directory that
+    /// the asset source of the `ResourceManager` reads from. That way the names don't depend on
+    /// how the paths were written, e.g. with `images` as `base` both `./images/ui/a.png` and
+    /// `images/ui/a.png` are named `ui/a.png`.
+    pub fn add_file(&mut self, base: &Path, path: &Path, insets: (f32, f32, f32, f32)) -> Result<()> {
+        let name = try!(get_relative_name(base, path));
+        let img = try!(image::open(path));
+        self.add_image(&name, img, insets);
+        Ok(())
+    }
+
+    /// Writes `<name><page>.png` for every page and the manifest `<name>.atlas` into `directory`.
+    pub fn bake(&self, directory: &Path, name: &str) -> Result<BakedAtlas> {
+        let mut order: Vec<usize> = (0..self.images.len()).collect();
+        order.sort_by(|a, b| self.images[*b].1.dimensions().1.cmp(&self.images[*a].1.dimensions().1));
+
+        let mut pages: Vec<TextureAtlas> = Vec::new();
+        let mut result = BakedAtlas {
+            pages: Vec::new(),
+            regions: Vec::new(),
+        };
+
+        for i in order {
+            let (ref image_name, ref img, insets) = self.images[i];
+            let (width, height) = img.dimensions();
+
+            let mut placed = None;
+            for (page, atlas) in pages.iter_mut().enumerate() {
+                if let Some(region) = atlas.get_region(width as usize, height as usize) {
+                    placed = Some((page, region));
+                    break;
+                }
+            }
+            let (page, region) = match placed {
+                Some(placed) => placed,
+                None => {
+                    let mut atlas = TextureAtlas::new(self.dimension, self.dimension, 4);
+                    atlas.set_gutter(self.gutter);
+                    let region = match atlas.get_region(width as usize, height as usize) {
+                        Some(region) => region,
+                        None => return Err(ResourceManagerError::TooLarge),
+                    };
+                    pages.push(atlas);
+                    (pages.len() - 1, region)
+                }
+            };
+
+            pages[page].set_region(region.0, region.1, region.2, region.3, &img.to_rgba().into_raw(), width as usize * 4);
+            let dimension = self.dimension as f32;
+            result.regions.push(BakedRegion {
+                name: image_name.clone(),
+                page: page,
+                position: (region.0, region.1),
+                size: (width, height),
+                uv_min: (region.0 as f32 / dimension, region.1 as f32 / dimension),
+                uv_max: ((region.0 as f32 + width as f32) / dimension, (region.1 as f32 + height as f32) / dimension),
+                insets: insets,
+            });
+        }
+
+        for (i, atlas) in pages.iter().enumerate() {
+            let page_name = format!("{}{}.png", name, i);
+            try!(atlas.save_to_file(&directory.join(&page_name)));
+            result.pages.push((self.dimension, self.dimension, page_name));
+        }
+        try!(result.save(&directory.join(format!("{}.atlas", name))));
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use super::get_relative_name;
+
+    #[test]
+    fn names_are_relative_to_base() {
+        let name = |base: &str, path: &str| get_relative_name(Path::new(base), Path::new(path)).ok();
+        assert_eq!(name("images", "images/ui/a.png"), Some("ui/a.png".to_string()));
+        assert_eq!(name("./images", "images/./ui/a.png"), Some("ui/a.png".to_string()));
+        assert_eq!(name("images/", "./images/a.png"), Some("a.png".to_string()));
+        assert_eq!(name("images", "other/a.png"), None);
+        assert_eq!(name("images", "images/../a.png"), None);
+        assert_eq!(name("images", "images"), None);
+    }
+}
@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+use std::str;
+use super::resource_manager::{Result, ResourceManagerError};
+
+/// A character of an AngelCode BMFont. Offsets are measured from the top of the line in pixels.
+#[derive(Debug, Clone)]
+pub struct BitmapChar {
+    pub id: u32,
+    pub page: usize,
+    pub position: (u32, u32),
+    pub size: (u32, u32),
+    pub offset: (f32, f32),
+    pub advance_x: f32,
+}
+
+/// Font description of an AngelCode BMFont (`.fnt`) file in the text or the binary format. Page
+/// file names are relative to the `.fnt` file.
+#[derive(Debug)]
+pub struct BitmapFont {
+    pub size: f32,
+    pub line_height: f32,
+    pub base: f32,
+    pub pages: Vec<String>,
+    pub chars: Vec<BitmapChar>,
+    pub kernings: Vec<(u32, u32, f32)>,
+}
+
+impl BitmapFont {
+    /// Parses a `.fnt` file, `name` is only used for error messages.
+    pub fn parse(data: &[u8], name: &str) -> Result<BitmapFont> {
+        if data.starts_with(b"BMF") {
+            BitmapFont::parse_binary(data, name)
+        } else {
+            match str::from_utf8(data) {
+                Ok(text) => BitmapFont::parse_text(text, name),
+                Err(_) => Err(ResourceManagerError::InvalidFont(name.to_string())),
+            }
+        }
+    }
+
+    fn new() -> BitmapFont {
+        BitmapFont {
+            size: 0.0,
+            line_height: 0.0,
+            base: 0.0,
+            pages: Vec::new(),
+            chars: Vec::new(),
+            kernings: Vec::new(),
+        }
+    }
+
+    fn parse_text(text: &str, name: &str) -> Result<BitmapFont> {
+        let mut result = BitmapFont::new();
+        let mut pages = HashMap::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let invalid = || ResourceManagerError::InvalidFont(format!("{}: invalid line {}", name, i + 1));
+            let (tag, attributes) = match split_line(line) {
+                Some(split) => split,
+                None => continue,
+            };
+            let number = |key: &str| -> Result<f32> {
+                match attributes.get(key).and_then(|value| value.parse::<f32>().ok()) {
+                    Some(value) => Ok(value),
+                    None => Err(invalid()),
+                }
+            };
+
+            match tag {
+                "info" => result.size = try!(number("size")).abs(),
+                "common" => {
+                    result.line_height = try!(number("lineHeight"));
+                    result.base = try!(number("base"));
+                }
+                "page" => {
+                    let id = try!(number("id")) as usize;
+                    let file = try!(attributes.get("file").ok_or_else(|| invalid()));
+                    pages.insert(id, file.clone());
+                }
+                "char" => {
+                    result.chars.push(BitmapChar {
+                        id: try!(number("id")) as u32,
+                        page: try!(number("page")) as usize,
+                        position: (try!(number("x")) as u32, try!(number("y")) as u32),
+                        size: (try!(number("width")) as u32, try!(number("height")) as u32),
+                        offset: (try!(number("xoffset")), try!(number("yoffset"))),
+                        advance_x: try!(number("xadvance")),
+                    });
+                }
+                "kerning" => {
+                    result.kernings.push((try!(number("first")) as u32, try!(number("second")) as u32, try!(number("amount"))));
+                }
+                _ => {}
+            }
+        }
+
+        for i in 0..pages.len() {
+            match pages.remove(&i) {
+                Some(page) => result.pages.push(page),
+                None => return Err(ResourceManagerError::InvalidFont(format!("{}: page {} is missing", name, i))),
+            }
+        }
+        try!(result.validate(name));
+        Ok(result)
+    }
+
+    fn parse_binary(data: &[u8], name: &str) -> Result<BitmapFont> {
+        let invalid = || ResourceManagerError::InvalidFont(format!("{}: invalid binary font", name));
+        if data.len() < 4 || data[3] != 3 {
+            return Err(ResourceManagerError::InvalidFont(format!("{}: only version 3 of the binary format is supported", name)));
+        }
+
+        let mut result = BitmapFont::new();
+        let mut position = 4;
+        while position < data.len() {
+            if position + 5 > data.len() {
+                return Err(invalid());
+            }
+            let block_type = data[position];
+            let block_size = read_u32(data, position + 1) as usize;
+            position += 5;
+            if position + block_size > data.len() {
+                return Err(invalid());
+            }
+            let block = &data[position..position + block_size];
+            position += block_size;
+
+            match block_type {
+                1 if block.len() >= 2 => result.size = (read_u16(block, 0) as i16 as f32).abs(),
+                2 if block.len() >= 4 => {
+                    result.line_height = read_u16(block, 0) as f32;
+                    result.base = read_u16(block, 2) as f32;
+                }
+                3 => {
+                    for page in block.split(|b| *b == 0).filter(|page| !page.is_empty()) {
+                        match str::from_utf8(page) {
+                            Ok(page) => result.pages.push(page.to_string()),
+                            Err(_) => return Err(invalid()),
+                        }
+                    }
+                }
+                4 => {
+                    for c in block.chunks(20).filter(|c| c.len() == 20) {
+                        result.chars.push(BitmapChar {
+                            id: read_u32(c, 0),
+                            page: c[18] as usize,
+                            position: (read_u16(c, 4) as u32, read_u16(c, 6) as u32),
+                            size: (read_u16(c, 8) as u32, read_u16(c, 10) as u32),
+                            offset: (read_u16(c, 12) as i16 as f32, read_u16(c, 14) as i16 as f32),
+                            advance_x: read_u16(c, 16) as i16 as f32,
+                        });
+                    }
+                }
+                5 => {
+                    for k in block.chunks(10).filter(|k| k.len() == 10) {
+                        result.kernings.push((read_u32(k, 0), read_u32(k, 4), read_u16(k, 8) as i16 as f32));
+                    }
+                }
+                _ => {}
+            }
+        }
+        try!(result.validate(name));
+        Ok(result)
+    }
+
+    fn validate(&self, name: &str) -> Result<()> {
+        if self.pages.is_empty() {
+            return Err(ResourceManagerError::InvalidFont(format!("{}: no pages", name)));
+        }
+        if let Some(c) = self.chars.iter().find(|c| c.page >= self.pages.len()) {
+            return Err(ResourceManagerError::InvalidFont(format!("{}: character {} is on missing page {}", name, c.id, c.page)));
+        }
+        Ok(())
+    }
+}
+
+fn read_u16(data: &[u8], position: usize) -> u16 {
+    data[position] as u16 | (data[position + 1] as u16) << 8
+}
+
+fn read_u32(data: &[u8], position: usize) -> u32 {
+    read_u16(data, position) as u32 | (read_u16(data, position + 2) as u32) << 16
+}
+
+/// Splits a line like `page id=0 file="font 0.png"` into its tag and attributes.
+fn split_line(line: &str) -> Option<(&str, HashMap<String, String>)> {
+    let line = line.trim();
+    let (tag, mut rest) = match line.find(' ') {
+        Some(i) => (&line[..i], line[i..].trim_left()),
+        None if !line.is_empty() => (line, ""),
+        None => return None,
+    };
+
+    let mut attributes = HashMap::new();
+    while let Some(i) = rest.find('=') {
+        let key = rest[..i].trim().to_string();
+        rest = &rest[i + 1..];
+        let value;
+        if rest.starts_with('"') {
+            let end = rest[1..].find('"').map_or(rest.len(), |end| end + 1);
+            value = rest[1..end].to_string();
+            rest = if end < rest.len() { &rest[end + 1..] } else { "" };
+        } else {
+            let end = rest.find(' ').unwrap_or(rest.len());
+            value = rest[..end].to_string();
+            rest = &rest[end..];
+        }
+        attributes.insert(key, value);
+        rest = rest.trim_left();
+    }
+    Some((tag, attributes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BitmapFont, split_line};
+
+    const TEXT_FONT: &'static str = "info face=\"Test Font\" size=-16 bold=0\n\
+                                     common lineHeight=19 base=15 scaleW=256 scaleH=256 pages=1\n\
+                                     page id=0 file=\"test 0.png\"\n\
+                                     chars count=1\n\
+                                     char id=65 x=10 y=20 width=8 height=9 xoffset=-1 yoffset=3 xadvance=7 page=0 chnl=15\n\
+                                     kernings count=1\n\
+                                     kerning first=65 second=86 amount=-2\n";
+
+    fn check_font(font: &BitmapFont, page: &str) {
+        assert_eq!(font.size, 16.0);
+        assert_eq!(font.line_height, 19.0);
+        assert_eq!(font.base, 15.0);
+        assert_eq!(font.pages, vec![page.to_string()]);
+        assert_eq!(font.chars.len(), 1);
+        let c = &font.chars[0];
+        assert_eq!((c.id, c.page), (65, 0));
+        assert_eq!((c.position, c.size), ((10, 20), (8, 9)));
+        assert_eq!((c.offset, c.advance_x), ((-1.0, 3.0), 7.0));
+        assert_eq!(font.kernings, vec![(65, 86, -2.0)]);
+    }
+
+    #[test]
+    fn quoted_attributes_keep_spaces() {
+        let (tag, attributes) = split_line("page id=0 file=\"test 0.png\"").unwrap();
+        assert_eq!(tag, "page");
+        assert_eq!(attributes["id"], "0");
+        assert_eq!(attributes["file"], "test 0.png");
+        assert!(split_line("   ").is_none());
+    }
+
+    #[test]
+    fn text_format() {
+        let font = BitmapFont::parse(TEXT_FONT.as_bytes(), "test.fnt").unwrap();
+        check_font(&font, "test 0.png");
+    }
+
+    #[test]
+    fn text_format_char_on_missing_page() {
+        let text = TEXT_FONT.replace("page=0 chnl", "page=1 chnl");
+        assert!(BitmapFont::parse(text.as_bytes(), "test.fnt").is_err());
+    }
+
+    fn block(block_type: u8, data: &[u8]) -> Vec<u8> {
+        let size = data.len() as u32;
+        let mut result = vec![block_type, size as u8, (size >> 8) as u8, (size >> 16) as u8, (size >> 24) as u8];
+        result.extend_from_slice(data);
+        result
+    }
+
+    fn binary_font() -> Vec<u8> {
+        let mut data = b"BMF\x03".to_vec();
+        // size is stored negative when it is the cell height
+        data.extend(block(1, &[0xf0, 0xff]));
+        data.extend(block(2, &[19, 0, 15, 0]));
+        data.extend(block(3, b"test_0.png\0"));
+        data.extend(block(4, &[65, 0, 0, 0, 10, 0, 20, 0, 8, 0, 9, 0, 0xff, 0xff, 3, 0, 7, 0, 0, 15]));
+        data.extend(block(5, &[65, 0, 0, 0, 86, 0, 0, 0, 0xfe, 0xff]));
+        data
+    }
+
+    #[test]
+    fn binary_format() {
+        let font = BitmapFont::parse(&binary_font(), "test.fnt").unwrap();
+        check_font(&font, "test_0.png");
+    }
+
+    #[test]
+    fn binary_format_rejects_truncated_block() {
+        let data = binary_font();
+        assert!(BitmapFont::parse(&data[..data.len() - 3], "test.fnt").is_err());
+        let mut data = binary_font();
+        data[3] = 2;
+        assert!(BitmapFont::parse(&data, "test.fnt").is_err());
+    }
+}
@@ -0,0 +1,43 @@
+/// Turns a coverage bitmap into a signed distance field that is `spread` pixels larger on every
+/// side. Values above 128 are inside the glyph, 128 is the edge and the field falls off to 0 and
+/// 255 at a distance of `spread` pixels.
+pub fn generate(coverage: &[u8], width: usize, height: usize, stride: usize, spread: usize) -> Vec<u8> {
+    let out_width = width + 2 * spread;
+    let out_height = height + 2 * spread;
+    let inside = |x: isize, y: isize| -> bool {
+        if x < 0 || y < 0 || x >= width as isize || y >= height as isize {
+            return false;
+        }
+        coverage[x as usize + y as usize * stride] >= 128
+    };
+
+    let mut result = vec![0; out_width * out_height];
+    let spread = spread as isize;
+    for out_y in 0..out_height {
+        for out_x in 0..out_width {
+            let x = out_x as isize - spread;
+            let y = out_y as isize - spread;
+            let is_inside = inside(x, y);
+
+            // brute force search for the closest pixel on the other side of the edge, glyphs are
+            // small enough for this to not matter
+            let mut closest = (spread * spread) as f32;
+            for dy in -spread..spread + 1 {
+                for dx in -spread..spread + 1 {
+                    let distance = (dx * dx + dy * dy) as f32;
+                    if distance < closest && inside(x + dx, y + dy) != is_inside {
+                        closest = distance;
+                    }
+                }
+            }
+            // the edge lies halfway between the two pixels
+            let mut distance = (closest.sqrt() - 0.5).max(0.0) / spread as f32;
+            if !is_inside {
+                distance = -distance;
+            }
+            let value = (0.5 + distance * 0.5).max(0.0).min(1.0);
+            result[out_x + out_y * out_width] = (value * 255.0) as u8;
+        }
+    }
+    result
+}
@@ -0,0 +1,417 @@
+use std::cmp;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::cell::RefCell;
+use rusttype;
+use rusttype::{FontCollection, Pixels, point};
+use super::texture_atlas::TextureAtlas;
+use super::distance_field;
+use super::resource_manager::{Glyph, GlyphMode, FontMetrics, Result, ResourceManagerError};
+
+/// Glyphs are cached per face, size, glyph mode and character. The size is stored in 1/64
+/// pixels so the key can be hashed.
+type GlyphKey = (usize, u32, GlyphMode, char);
+
+fn size_key(size: f32) -> u32 {
+    (size * 64.0) as u32
+}
+
+struct Face {
+    path: String,
+    /// `None` for bitmap fonts, their glyphs are inserted up front and never evicted.
+    font: Option<rusttype::Font<'static>>,
+}
+
+struct GlyphPage {
+    atlas: Rc<RefCell<TextureAtlas>>,
+    mode: GlyphMode,
+    last_used: u64,
+}
+
+/// Glyphs of all fonts, packed into shared single channel atlas pages. Font files are parsed
+/// once per face no matter in how many sizes they are used. Once `max_pages` pages exist the
+/// least recently used page is cleared to make room, pages with bitmap font glyphs are kept.
+/// Glyphs that are too large for a page get a larger page of their own, it counts as one page.
+pub struct GlyphCache {
+    faces: Vec<Face>,
+    page_dimension: usize,
+    max_pages: usize,
+    pages: Vec<GlyphPage>,
+    glyphs: HashMap<GlyphKey, Glyph>,
+    kernings: HashMap<(usize, u32, char, char), f32>,
+    clock: u64,
+}
+
+/// Parses the first font of a font file or collection.
+pub fn parse_font(data: Vec<u8>) -> Option<rusttype::Font<'static>> {
+    FontCollection::from_bytes(data).into_font()
+}
+
+impl GlyphCache {
+    pub fn new(page_dimension: usize, max_pages: usize) -> Self {
+        GlyphCache {
+            faces: Vec::new(),
+            page_dimension: page_dimension,
+            max_pages: max_pages,
+            pages: Vec::new(),
+            glyphs: HashMap::new(),
+            kernings: HashMap::new(),
+            clock: 0,
+        }
+    }
+
+    pub fn set_max_pages(&mut self, max_pages: usize) {
+        self.max_pages = max_pages;
+    }
+
+    /// Returns the id of the face that was loaded from `path`.
+    pub fn get_face(&self, path: &str) -> Option<usize> {
+        self.faces.iter().position(|face| face.path == path)
+    }
+
+    /// Parses `data` and registers it as the face `path`.
+    pub fn add_face(&mut self, path: &str, data: Vec<u8>) -> Result<usize> {
+        let font = match parse_font(data) {
+            Some(font) => font,
+            None => return Err(ResourceManagerError::InvalidFont(path.to_string())),
+        };
+        self.faces.push(Face {
+            path: path.to_string(),
+            font: Some(font),
+        });
+        Ok(self.faces.len() - 1)
+    }
+
+    /// Registers a face for a bitmap font whose glyphs are added with `insert_bitmap_glyph`.
+    pub fn add_bitmap_face(&mut self, path: &str) -> usize {
+        self.faces.push(Face {
+            path: path.to_string(),
+            font: None,
+        });
+        self.faces.len() - 1
+    }
+
+    /// Replaces the font of a face, e.g. after the file changed. Cached glyphs of the face are
+    /// rasterized again the next time they are used.
+    pub fn replace_face(&mut self, face: usize, data: Vec<u8>) -> Result<()> {
+        let font = match parse_font(data) {
+            Some(font) => font,
+            None => return Err(ResourceManagerError::InvalidFont(self.faces[face].path.clone())),
+        };
+        self.faces[face].font = Some(font);
+        let keys: Vec<GlyphKey> = self.glyphs.keys().filter(|key| key.0 == face).cloned().collect();
+        for key in keys {
+            self.remove_glyph(&key);
+        }
+        let keys: Vec<_> = self.kernings.keys().filter(|key| key.0 == face).cloned().collect();
+        for key in keys {
+            self.kernings.remove(&key);
+        }
+        Ok(())
+    }
+
+    /// Reads the vertical metrics of `face` at `size`. x-height and cap height are the heights
+    /// of `x` and `H` since the tables that store them are optional.
+    pub fn get_metrics(&self, face: usize, size: f32) -> FontMetrics {
+        let font = match self.faces[face].font {
+            Some(ref font) => font,
+            None => return FontMetrics::new(),
+        };
+        let scale = Pixels(size);
+        let v_metrics = font.v_metrics(scale);
+        let glyph_height = |c: char| -> f32 {
+            font.glyph(c)
+                .and_then(|g| g.scaled(scale).exact_bounding_box())
+                .map_or(0.0, |bounding_box| bounding_box.max.y - bounding_box.min.y)
+        };
+        FontMetrics {
+            ascent: v_metrics.ascent,
+            descent: v_metrics.descent,
+            line_gap: v_metrics.line_gap,
+            line_height: v_metrics.ascent - v_metrics.descent + v_metrics.line_gap,
+            x_height: glyph_height('x'),
+            cap_height: glyph_height('H'),
+        }
+    }
+
+    /// Atlas and glyph mode of the page `page`.
+    pub fn get_page(&self, page: usize) -> (Rc<RefCell<TextureAtlas>>, GlyphMode) {
+        (self.pages[page].atlas.clone(), self.pages[page].mode)
+    }
+
+    /// Returns the glyph of `c` from the first of `faces` that has it, rasterizing it first if
+    /// necessary. Fails with `ResourceManagerError::TooLarge` if the glyph doesn't fit into a page.
+    pub fn get_glyph(&mut self, faces: &[usize], size: f32, mode: GlyphMode, c: char) -> Result<Option<Glyph>> {
+        self.clock += 1;
+        for &face in faces {
+            let key = (face, size_key(size), mode_for_face(&self.faces[face], mode), c);
+            if let Some(glyph) = self.glyphs.get(&key).cloned() {
+                if glyph.width != 0.0 {
+                    self.pages[glyph.page].last_used = self.clock;
+                }
+                return Ok(Some(glyph));
+            }
+            let has_glyph = match self.faces[face].font {
+                Some(ref font) => font.glyph(c).map_or(false, |g| g.id().0 != 0),
+                None => false,
+            };
+            if has_glyph {
+                return self.rasterize(key, size);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Kerning between two characters of `face`. Pairs are looked up in the kerning table of the
+    /// face the first time they are used and cached afterwards, bitmap fonts only know the pairs
+    /// from their file.
+    pub fn get_kerning(&mut self, face: usize, size: f32, first: char, second: char) -> f32 {
+        let key = (face, size_key(size), first, second);
+        if let Some(kerning) = self.kernings.get(&key) {
+            return *kerning;
+        }
+        let kerning = match self.faces[face].font {
+            Some(ref font) => font.pair_kerning(Pixels(size), first, second),
+            None => return 0.0,
+        };
+        // pairs without kerning are cached as well so they aren't looked up again
+        self.kernings.insert(key, kerning);
+        kerning
+    }
+
+    /// Copies a glyph of a bitmap font from `data` into a page. The uvs of `glyph` are set here.
+    pub fn insert_bitmap_glyph(&mut self, face: usize, size: f32, c: char, mut glyph: Glyph, data: &[u8], stride: usize) -> Result<()> {
+        let (width, height) = (glyph.width as usize, glyph.height as usize);
+        if width != 0 && height != 0 {
+            let (page, region) = match self.allocate(GlyphMode::Bitmap, width, height) {
+                Some(allocation) => allocation,
+                None => return Err(ResourceManagerError::TooLarge),
+            };
+            self.pages[page].atlas.borrow_mut().set_region(region.0, region.1, region.2, region.3, data, stride);
+            self.set_uvs(&mut glyph, page, region);
+        }
+        glyph.face = face;
+        self.glyphs.insert((face, size_key(size), GlyphMode::Bitmap, c), glyph);
+        Ok(())
+    }
+
+    pub fn insert_bitmap_kerning(&mut self, face: usize, size: f32, first: char, second: char, kerning: f32) {
+        self.kernings.insert((face, size_key(size), first, second), kerning);
+    }
+
+    /// Forgets the glyphs of `face` at `size` in `mode` and gives their space on the pages back.
+    /// Glyphs that are still used, e.g. as fallbacks of another font, are rasterized again.
+    pub fn remove_glyphs(&mut self, face: usize, size: f32, mode: GlyphMode) {
+        let mode = mode_for_face(&self.faces[face], mode);
+        let keys: Vec<GlyphKey> = self.glyphs.keys()
+            .filter(|key| key.0 == face && key.1 == size_key(size) && key.2 == mode)
+            .cloned().collect();
+        for key in keys {
+            self.remove_glyph(&key);
+        }
+        let keys: Vec<_> = self.kernings.keys().filter(|key| key.0 == face && key.1 == size_key(size)).cloned().collect();
+        for key in keys {
+            self.kernings.remove(&key);
+        }
+    }
+
+    /// Forgets a glyph and gives its space on its page back.
+    fn remove_glyph(&mut self, key: &GlyphKey) {
+        if let Some(glyph) = self.glyphs.remove(key) {
+            if glyph.width != 0.0 {
+                let mut atlas = self.pages[glyph.page].atlas.borrow_mut();
+                let (width, height) = atlas.get_dimensions();
+                atlas.free_region((glyph.uv_min.0 * width as f32).round() as usize,
+                                  (glyph.uv_min.1 * height as f32).round() as usize);
+            }
+        }
+    }
+
+    /// Number of cached glyphs of `face` at `size`.
+    pub fn get_glyph_count(&self, face: usize, size: f32) -> usize {
+        self.glyphs.keys().filter(|key| key.0 == face && key.1 == size_key(size)).count()
+    }
+
+    /// Atlases of the pages that contain glyphs of `face` at `size`.
+    pub fn get_atlases(&self, face: usize, size: f32) -> Vec<Rc<RefCell<TextureAtlas>>> {
+        let mut pages: Vec<usize> = self.glyphs.iter()
+            .filter(|&(key, glyph)| key.0 == face && key.1 == size_key(size) && glyph.width != 0.0)
+            .map(|(_, glyph)| glyph.page).collect();
+        pages.sort();
+        pages.dedup();
+        pages.iter().map(|page| self.pages[*page].atlas.clone()).collect()
+    }
+
+    /// Atlases of all pages.
+    pub fn get_page_atlases(&self) -> Vec<Rc<RefCell<TextureAtlas>>> {
+        self.pages.iter().map(|page| page.atlas.clone()).collect()
+    }
+
+    /// Bytes of a regular page.
+    pub fn get_page_bytes(&self) -> usize {
+        self.page_dimension * self.page_dimension
+    }
+
+    fn rasterize(&mut self, key: GlyphKey, size: f32) -> Result<Option<Glyph>> {
+        let (face, _, mode, c) = key;
+        let scale = Pixels(size);
+
+        let mut glyph = Glyph::new();
+        glyph.face = face;
+        let mut bitmap = None;
+        {
+            let font = match self.faces[face].font {
+                Some(ref font) => font,
+                None => return Ok(None),
+            };
+            let g = match font.glyph(c) {
+                Some(g) => g.scaled(scale),
+                None => return Ok(None),
+            };
+            glyph.advance_x = g.h_metrics().advance_width;
+            // whitespace characters don't have a bounding box
+            if let Some(exact_bounding_box) = g.exact_bounding_box() {
+                let pos_x: f32 = -(exact_bounding_box.min.x as f32).floor();
+                let pos_y: f32 = (exact_bounding_box.max.y as f32).ceil();
+                let g = g.positioned(point(pos_x, pos_y));
+                if let Some(pixel_bounding_box) = g.pixel_bounding_box() {
+                    let mut width = pixel_bounding_box.max.x as usize;
+                    let mut height = pixel_bounding_box.max.y as usize;
+                    let mut buf: Vec<u8> = vec![0; (width + 1) * (height + 1)];
+                    g.draw(|x, y, v| {
+                        buf[x as usize + y as usize * width] = (v * 255.0) as u8;
+                    });
+                    let mut padding = 0.0;
+                    if let GlyphMode::DistanceField { spread } = mode {
+                        buf = distance_field::generate(&buf, width, height, width, spread);
+                        width += 2 * spread;
+                        height += 2 * spread;
+                        padding = spread as f32;
+                    }
+                    glyph.offset = (-pos_x - padding, pos_y + padding);
+                    bitmap = Some((buf, width, height));
+                }
+            }
+        }
+
+        if let Some((buf, width, height)) = bitmap {
+            let (page, region) = match self.allocate(mode, width, height) {
+                Some(allocation) => allocation,
+                None => return Err(ResourceManagerError::TooLarge),
+            };
+            self.pages[page].atlas.borrow_mut().set_region(region.0, region.1, region.2, region.3, &buf, width);
+            self.set_uvs(&mut glyph, page, region);
+        }
+        self.glyphs.insert(key, glyph);
+        Ok(Some(glyph))
+    }
+
+    fn set_uvs(&self, glyph: &mut Glyph, page: usize, region: (usize, usize, usize, usize)) {
+        // pages for oversized glyphs are larger than the others
+        let (width, height) = self.pages[page].atlas.borrow().get_dimensions();
+        let (width, height) = (width as f32, height as f32);
+        glyph.page = page;
+        glyph.uv_min = (region.0 as f32 / width, region.1 as f32 / height);
+        glyph.uv_max = ((region.0 + region.2) as f32 / width, (region.1 + region.3) as f32 / height);
+        glyph.width = region.2 as f32;
+        glyph.height = region.3 as f32;
+    }
+
+    /// Finds room for a glyph on a page of the given mode, creating or evicting a page if
+    /// necessary.
+    fn allocate(&mut self, mode: GlyphMode, width: usize, height: usize) -> Option<(usize, (usize, usize, usize, usize))> {
+        // the packers keep a 1 pixel border free, glyphs that don't fit into a regular page get a
+        // page of their own instead of being dropped
+        if width + 2 > self.page_dimension || height + 2 > self.page_dimension {
+            let dimension = (cmp::max(width, height) + 2).next_power_of_two();
+            let atlas = Rc::new(RefCell::new(TextureAtlas::new(dimension, dimension, 1)));
+            // such a page counts against `max_pages` like any other page
+            let page = match self.get_evictable_page() {
+                Some(page) => {
+                    self.evict(page);
+                    self.pages[page].atlas = atlas;
+                    page
+                }
+                None => {
+                    self.pages.push(GlyphPage {
+                        atlas: atlas,
+                        mode: mode,
+                        last_used: self.clock,
+                    });
+                    self.pages.len() - 1
+                }
+            };
+            self.pages[page].mode = mode;
+            self.pages[page].last_used = self.clock;
+            let region = self.pages[page].atlas.borrow_mut().get_region(width, height);
+            return region.map(|region| (page, region));
+        }
+
+        for (i, page) in self.pages.iter_mut().enumerate() {
+            if page.mode == mode {
+                if let Some(region) = page.atlas.borrow_mut().get_region(width, height) {
+                    page.last_used = self.clock;
+                    return Some((i, region));
+                }
+            }
+        }
+
+        let page = match self.get_evictable_page() {
+            Some(page) => {
+                self.evict(page);
+                // an oversized page goes back to the regular size
+                if self.pages[page].atlas.borrow().get_dimensions().0 != self.page_dimension {
+                    let atlas = TextureAtlas::new(self.page_dimension, self.page_dimension, 1);
+                    self.pages[page].atlas = Rc::new(RefCell::new(atlas));
+                }
+                page
+            }
+            None => {
+                // over budget if bitmap fonts take up all the pages
+                self.pages.push(GlyphPage {
+                    atlas: Rc::new(RefCell::new(TextureAtlas::new(self.page_dimension, self.page_dimension, 1))),
+                    mode: mode,
+                    last_used: self.clock,
+                });
+                self.pages.len() - 1
+            }
+        };
+        self.pages[page].mode = mode;
+        self.pages[page].last_used = self.clock;
+        let region = self.pages[page].atlas.borrow_mut().get_region(width, height);
+        region.map(|region| (page, region))
+    }
+
+    /// Returns the least recently used page that may be evicted, once `max_pages` pages exist.
+    /// Pages with bitmap font glyphs are never evicted.
+    fn get_evictable_page(&self) -> Option<usize> {
+        if self.pages.len() < self.max_pages {
+            return None;
+        }
+        self.pages.iter().enumerate()
+            .filter(|&(_, page)| page.mode != GlyphMode::Bitmap)
+            .min_by_key(|&(_, page)| page.last_used)
+            .map(|(i, _)| i)
+    }
+
+    /// Clears a page and forgets the glyphs on it, they are rasterized again when needed.
+    fn evict(&mut self, page: usize) {
+        self.pages[page].atlas.borrow_mut().clear();
+        let keys: Vec<GlyphKey> = self.glyphs.iter()
+            .filter(|&(_, glyph)| glyph.width != 0.0 && glyph.page == page)
+            .map(|(key, _)| *key).collect();
+        for key in keys {
+            self.glyphs.remove(&key);
+        }
+    }
+}
+
+/// Fallback faces of a bitmap font are rasterized as coverage, bitmap glyphs are stored under
+/// `GlyphMode::Bitmap` whatever the mode of the font that uses them.
+fn mode_for_face(face: &Face, mode: GlyphMode) -> GlyphMode {
+    match (face.font.is_some(), mode) {
+        (true, GlyphMode::Bitmap) => GlyphMode::Coverage,
+        (true, mode) => mode,
+        (false, _) => GlyphMode::Bitmap,
+    }
+}
@@ -1,5 +1,20 @@
 pub use self::texture_atlas::TextureAtlas;
-pub use self::resource_manager::{ResourceManager, Texture, Font};
+pub use self::packing::{Packer, PackerStats, PackingStrategy, SkylinePacker, MaxRectsPacker,
+                        GuillotinePacker, ShelfPacker};
+pub use self::asset_source::{AssetSource, DirectorySource, ZipSource, MemorySource};
+pub use self::baked_atlas::{AtlasBaker, BakedAtlas, BakedRegion};
+pub use self::resource_manager::{ResourceManager, ResourceManagerError, Texture, TextureHandle, TextureTransform, LoadState,
+                                 Font, FontHandle, Glyph, FontMetrics, GlyphMode, TextureInfo, FontInfo,
+                                 ResourceCategory, AtlasStats, SpriteSheet, SpriteFrame};
 
 mod resource_manager;
 mod texture_atlas;
+mod packing;
+mod baked_atlas;
+mod asset_source;
+mod async_loader;
+mod distance_field;
+mod bitmap_font;
+mod glyph_cache;
+mod slots;
+mod sprite_sheet;
@@ -0,0 +1,109 @@
+use std::usize;
+use std::cmp;
+
+/// List of free rectangles that regions are cut from using the best area fit heuristic. The
+/// leftover space is split along the shorter axis. Rectangles that are added back are merged with
+/// free neighbours they share a whole edge with.
+pub struct FreeList {
+    rects: Vec<(usize, usize, usize, usize)>,
+}
+
+impl FreeList {
+    pub fn new() -> Self {
+        FreeList {
+            rects: Vec::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.rects.clear();
+    }
+
+    pub fn take(&mut self, width: usize, height: usize) -> Option<(usize, usize, usize, usize)> {
+        let (mut best_area, mut best_index) = (usize::MAX, usize::MAX);
+
+        for (i, &(_, _, free_width, free_height)) in self.rects.iter().enumerate() {
+            if width <= free_width && height <= free_height {
+                let area = free_width * free_height - width * height;
+                if area < best_area {
+                    best_area = area;
+                    best_index = i;
+                }
+            }
+        }
+
+        if best_index == usize::MAX {
+            return None;
+        }
+
+        let (x, y, free_width, free_height) = self.rects.swap_remove(best_index);
+        let (leftover_x, leftover_y) = (free_width - width, free_height - height);
+        let (right, bottom) = if leftover_x < leftover_y {
+            ((x + width, y, leftover_x, height), (x, y + height, free_width, leftover_y))
+        } else {
+            ((x + width, y, leftover_x, free_height), (x, y + height, width, leftover_y))
+        };
+        if right.2 > 0 && right.3 > 0 {
+            self.rects.push(right);
+        }
+        if bottom.2 > 0 && bottom.3 > 0 {
+            self.rects.push(bottom);
+        }
+        Some((x, y, width, height))
+    }
+
+    pub fn add(&mut self, rect: (usize, usize, usize, usize)) {
+        let mut rect = rect;
+        // a merged rectangle can line up with another one
+        loop {
+            let merged = self.rects.iter().enumerate()
+                .filter_map(|(i, &other)| merge(rect, other).map(|merged| (i, merged)))
+                .next();
+            match merged {
+                Some((i, merged)) => {
+                    self.rects.swap_remove(i);
+                    rect = merged;
+                }
+                None => break,
+            }
+        }
+        self.rects.push(rect);
+    }
+}
+
+fn merge(a: (usize, usize, usize, usize), b: (usize, usize, usize, usize)) -> Option<(usize, usize, usize, usize)> {
+    if a.1 == b.1 && a.3 == b.3 && (a.0 + a.2 == b.0 || b.0 + b.2 == a.0) {
+        Some((cmp::min(a.0, b.0), a.1, a.2 + b.2, a.3))
+    } else if a.0 == b.0 && a.2 == b.2 && (a.1 + a.3 == b.1 || b.1 + b.3 == a.1) {
+        Some((a.0, cmp::min(a.1, b.1), a.2, a.3 + b.3))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FreeList;
+
+    #[test]
+    fn takes_best_area_fit() {
+        let mut free_list = FreeList::new();
+        assert_eq!(free_list.take(1, 1), None);
+        free_list.add((0, 0, 8, 8));
+        free_list.add((20, 0, 5, 5));
+        assert_eq!(free_list.take(4, 4), Some((20, 0, 4, 4)));
+        assert_eq!(free_list.take(9, 1), None);
+    }
+
+    #[test]
+    fn returned_rects_merge_back() {
+        let mut free_list = FreeList::new();
+        free_list.add((0, 0, 10, 10));
+        assert_eq!(free_list.take(4, 4), Some((0, 0, 4, 4)));
+        assert_eq!(free_list.take(6, 10), Some((4, 0, 6, 10)));
+        assert_eq!(free_list.take(10, 10), None);
+        free_list.add((0, 0, 4, 4));
+        free_list.add((4, 0, 6, 10));
+        assert_eq!(free_list.take(10, 10), Some((0, 0, 10, 10)));
+    }
+}
@@ -0,0 +1,59 @@
+use super::{Packer, PackerStats};
+use super::free_list::FreeList;
+
+/// Guillotine packer using the best area fit heuristic. The free rectangle a region is placed in
+/// gets split along the shorter leftover axis, freed regions are merged with their neighbours.
+pub struct GuillotinePacker {
+    width: usize,
+    height: usize,
+    free_rects: FreeList,
+    used: usize,
+    regions: usize,
+}
+
+impl GuillotinePacker {
+    pub fn new(width: usize, height: usize) -> Self {
+        let mut result = GuillotinePacker {
+            width: width,
+            height: height,
+            free_rects: FreeList::new(),
+            used: 0,
+            regions: 0,
+        };
+        result.free_rects.add((1, 1, width - 2, height - 2));
+        result
+    }
+}
+
+impl Packer for GuillotinePacker {
+    fn get_region(&mut self, width: usize, height: usize) -> Option<(usize, usize, usize, usize)> {
+        let region = match self.free_rects.take(width, height) {
+            Some(region) => region,
+            None => return None,
+        };
+        self.used += width * height;
+        self.regions += 1;
+        Some(region)
+    }
+
+    fn free_region(&mut self, region: (usize, usize, usize, usize)) {
+        self.free_rects.add(region);
+        self.used -= region.2 * region.3;
+        self.regions -= 1;
+    }
+
+    fn clear(&mut self) {
+        self.free_rects.clear();
+        self.free_rects.add((1, 1, self.width - 2, self.height - 2));
+        self.used = 0;
+        self.regions = 0;
+    }
+
+    fn get_stats(&self) -> PackerStats {
+        PackerStats {
+            total_area: (self.width - 2) * (self.height - 2),
+            used_area: self.used,
+            regions: self.regions,
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use std::usize;
+use std::cmp;
+use super::{Packer, PackerStats};
+
+/// MaxRects packer using the best short side fit heuristic. Keeps a list of maximal free
+/// rectangles which may overlap each other.
+pub struct MaxRectsPacker {
+    width: usize,
+    height: usize,
+    free_rects: Vec<(usize, usize, usize, usize)>,
+    used: usize,
+    regions: usize,
+}
+
+impl MaxRectsPacker {
+    pub fn new(width: usize, height: usize) -> Self {
+        let mut result = MaxRectsPacker {
+            width: width,
+            height: height,
+            free_rects: Vec::new(),
+            used: 0,
+            regions: 0,
+        };
+        result.free_rects.push((1, 1, width - 2, height - 2));
+        result
+    }
+
+    fn split_free_rect(free: (usize, usize, usize, usize), used: (usize, usize, usize, usize),
+                       result: &mut Vec<(usize, usize, usize, usize)>) -> bool {
+        let (fx, fy, fw, fh) = free;
+        let (ux, uy, uw, uh) = used;
+        if ux >= fx + fw || ux + uw <= fx || uy >= fy + fh || uy + uh <= fy {
+            return false;
+        }
+
+        if ux > fx {
+            result.push((fx, fy, ux - fx, fh));
+        }
+        if ux + uw < fx + fw {
+            result.push((ux + uw, fy, fx + fw - (ux + uw), fh));
+        }
+        if uy > fy {
+            result.push((fx, fy, fw, uy - fy));
+        }
+        if uy + uh < fy + fh {
+            result.push((fx, uy + uh, fw, fy + fh - (uy + uh)));
+        }
+        true
+    }
+
+    fn contains(a: (usize, usize, usize, usize), b: (usize, usize, usize, usize)) -> bool {
+        b.0 >= a.0 && b.1 >= a.1 && b.0 + b.2 <= a.0 + a.2 && b.1 + b.3 <= a.1 + a.3
+    }
+
+    fn prune_free_rects(&mut self) {
+        let mut i = 0;
+        while i < self.free_rects.len() {
+            let mut removed = false;
+            let mut j = i + 1;
+            while j < self.free_rects.len() {
+                if MaxRectsPacker::contains(self.free_rects[j], self.free_rects[i]) {
+                    self.free_rects.remove(i);
+                    removed = true;
+                    break;
+                }
+                if MaxRectsPacker::contains(self.free_rects[i], self.free_rects[j]) {
+                    self.free_rects.remove(j);
+                }
+                else {
+                    j += 1;
+                }
+            }
+            if !removed {
+                i += 1;
+            }
+        }
+    }
+}
+
+impl Packer for MaxRectsPacker {
+    fn get_region(&mut self, width: usize, height: usize) -> Option<(usize, usize, usize, usize)> {
+        let mut region = None;
+        let (mut best_short_side, mut best_long_side) = (usize::MAX, usize::MAX);
+
+        for &(x, y, free_width, free_height) in &self.free_rects {
+            if width <= free_width && height <= free_height {
+                let (leftover_x, leftover_y) = (free_width - width, free_height - height);
+                let short_side = cmp::min(leftover_x, leftover_y);
+                let long_side = cmp::max(leftover_x, leftover_y);
+                if short_side < best_short_side || (short_side == best_short_side && long_side < best_long_side) {
+                    best_short_side = short_side;
+                    best_long_side = long_side;
+                    region = Some((x, y, width, height));
+                }
+            }
+        }
+
+        let region = match region {
+            Some(region) => region,
+            None => return None,
+        };
+
+        let mut new_rects = Vec::new();
+        let mut i = 0;
+        while i < self.free_rects.len() {
+            if MaxRectsPacker::split_free_rect(self.free_rects[i], region, &mut new_rects) {
+                self.free_rects.swap_remove(i);
+            }
+            else {
+                i += 1;
+            }
+        }
+        self.free_rects.extend(new_rects);
+        self.prune_free_rects();
+
+        self.used += width * height;
+        self.regions += 1;
+        Some(region)
+    }
+
+    fn free_region(&mut self, region: (usize, usize, usize, usize)) {
+        // the region can overlap maximal free rectangles next to it, pruning removes the ones
+        // it now contains
+        self.free_rects.push(region);
+        self.prune_free_rects();
+        self.used -= region.2 * region.3;
+        self.regions -= 1;
+    }
+
+    fn clear(&mut self) {
+        self.free_rects.clear();
+        self.free_rects.push((1, 1, self.width - 2, self.height - 2));
+        self.used = 0;
+        self.regions = 0;
+    }
+
+    fn get_stats(&self) -> PackerStats {
+        PackerStats {
+            total_area: (self.width - 2) * (self.height - 2),
+            used_area: self.used,
+            regions: self.regions,
+        }
+    }
+}
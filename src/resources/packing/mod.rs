@@ -0,0 +1,132 @@
+pub use self::skyline::SkylinePacker;
+pub use self::max_rects::MaxRectsPacker;
+pub use self::guillotine::GuillotinePacker;
+pub use self::shelf::ShelfPacker;
+
+mod skyline;
+mod max_rects;
+mod guillotine;
+mod shelf;
+mod free_list;
+
+/// Allocates rectangular regions inside an area of `width` x `height` pixels. All packers keep
+/// a 1 pixel border around the area free.
+pub trait Packer {
+    /// Returns the region as `(x, y, width, height)` or `None` if it doesn't fit anymore.
+    fn get_region(&mut self, width: usize, height: usize) -> Option<(usize, usize, usize, usize)>;
+    /// Gives a region that was returned by `get_region` back, so its space can be reused.
+    fn free_region(&mut self, region: (usize, usize, usize, usize));
+    fn clear(&mut self);
+    fn get_stats(&self) -> PackerStats;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PackerStats {
+    pub total_area: usize,
+    pub used_area: usize,
+    pub regions: usize,
+}
+
+impl PackerStats {
+    /// Fraction of the packable area that is covered by regions.
+    pub fn get_occupancy(&self) -> f32 {
+        if self.total_area == 0 {
+            return 0.0;
+        }
+        self.used_area as f32 / self.total_area as f32
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackingStrategy {
+    Skyline,
+    MaxRects,
+    Guillotine,
+    Shelf,
+}
+
+impl PackingStrategy {
+    pub fn create_packer(&self, width: usize, height: usize) -> Box<Packer> {
+        match *self {
+            PackingStrategy::Skyline => Box::new(SkylinePacker::new(width, height)),
+            PackingStrategy::MaxRects => Box::new(MaxRectsPacker::new(width, height)),
+            PackingStrategy::Guillotine => Box::new(GuillotinePacker::new(width, height)),
+            PackingStrategy::Shelf => Box::new(ShelfPacker::new(width, height)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image;
+    use image::GenericImage;
+    use super::{Packer, PackingStrategy};
+
+    const ATLAS_DIMENSION: usize = 512;
+    const EXAMPLE_IMAGES: [&'static str; 8] = ["button_shadow.png", "dock.png", "dock_tab_deselected.png",
+                                               "dock_tab_selected.png", "inferno-logo.png", "inferno-logo2.png",
+                                               "rust-logo.png", "window_sq.png"];
+
+    /// Sizes of the example images, repeated until they no longer fit into one atlas.
+    fn example_sizes() -> Vec<(usize, usize)> {
+        let mut sizes = Vec::new();
+        for name in EXAMPLE_IMAGES.iter() {
+            let img = image::open(&format!("example_images/{}", name)).unwrap();
+            let (width, height) = img.dimensions();
+            sizes.push((width as usize, height as usize));
+        }
+        let mut result = Vec::new();
+        for _ in 0..8 {
+            result.extend_from_slice(&sizes);
+        }
+        // tallest first, like ResourceManager::compact_atlases
+        result.sort_by(|a, b| b.1.cmp(&a.1));
+        result
+    }
+
+    fn check_packer(strategy: PackingStrategy) {
+        let mut packer = strategy.create_packer(ATLAS_DIMENSION, ATLAS_DIMENSION);
+        let mut regions: Vec<(usize, usize, usize, usize)> = Vec::new();
+        for (width, height) in example_sizes() {
+            if let Some(region) = packer.get_region(width, height) {
+                assert_eq!((region.2, region.3), (width, height));
+                regions.push(region);
+            }
+        }
+        assert!(!regions.is_empty());
+
+        for (i, a) in regions.iter().enumerate() {
+            assert!(a.0 >= 1 && a.1 >= 1, "{:?} is in the border", a);
+            assert!(a.0 + a.2 <= ATLAS_DIMENSION - 1 && a.1 + a.3 <= ATLAS_DIMENSION - 1, "{:?} is in the border", a);
+            for b in &regions[i + 1..] {
+                let overlaps = a.0 < b.0 + b.2 && b.0 < a.0 + a.2 && a.1 < b.1 + b.3 && b.1 < a.1 + a.3;
+                assert!(!overlaps, "{:?} overlaps {:?}", a, b);
+            }
+        }
+
+        let stats = packer.get_stats();
+        assert_eq!(stats.regions, regions.len());
+        // only one of the 256 pixel logos fits, the smaller images fill most of the rest
+        assert!(stats.get_occupancy() >= 0.5, "{:?} only covers {:.3} of the atlas", strategy, stats.get_occupancy());
+    }
+
+    #[test]
+    fn skyline() {
+        check_packer(PackingStrategy::Skyline);
+    }
+
+    #[test]
+    fn max_rects() {
+        check_packer(PackingStrategy::MaxRects);
+    }
+
+    #[test]
+    fn guillotine() {
+        check_packer(PackingStrategy::Guillotine);
+    }
+
+    #[test]
+    fn shelf() {
+        check_packer(PackingStrategy::Shelf);
+    }
+}
@@ -0,0 +1,95 @@
+use std::usize;
+use super::{Packer, PackerStats};
+use super::free_list::FreeList;
+
+/// Shelf packer. Regions are placed left to right on horizontal shelves, a new shelf is opened
+/// when no existing shelf has enough room.
+pub struct ShelfPacker {
+    width: usize,
+    height: usize,
+    // (y, height, used width)
+    shelves: Vec<(usize, usize, usize)>,
+    next_y: usize,
+    /// Space of freed regions, it is used before the rest of the area.
+    freed: FreeList,
+    used: usize,
+    regions: usize,
+}
+
+impl ShelfPacker {
+    pub fn new(width: usize, height: usize) -> Self {
+        ShelfPacker {
+            width: width,
+            height: height,
+            shelves: Vec::new(),
+            next_y: 1,
+            freed: FreeList::new(),
+            used: 0,
+            regions: 0,
+        }
+    }
+}
+
+impl Packer for ShelfPacker {
+    fn get_region(&mut self, width: usize, height: usize) -> Option<(usize, usize, usize, usize)> {
+        if let Some(region) = self.freed.take(width, height) {
+            self.used += width * height;
+            self.regions += 1;
+            return Some(region);
+        }
+
+        if width > self.width - 2 {
+            return None;
+        }
+
+        let (mut best_waste, mut best_index) = (usize::MAX, usize::MAX);
+        for (i, &(_, shelf_height, shelf_used)) in self.shelves.iter().enumerate() {
+            if height <= shelf_height && shelf_used + width <= self.width - 2 {
+                let waste = shelf_height - height;
+                if waste < best_waste {
+                    best_waste = waste;
+                    best_index = i;
+                }
+            }
+        }
+
+        if best_index == usize::MAX {
+            if self.next_y + height > self.height - 1 {
+                return None;
+            }
+            self.shelves.push((self.next_y, height, 0));
+            self.next_y += height;
+            best_index = self.shelves.len() - 1;
+        }
+
+        let shelf = &mut self.shelves[best_index];
+        let region = (1 + shelf.2, shelf.0, width, height);
+        shelf.2 += width;
+
+        self.used += width * height;
+        self.regions += 1;
+        Some(region)
+    }
+
+    fn free_region(&mut self, region: (usize, usize, usize, usize)) {
+        self.freed.add(region);
+        self.used -= region.2 * region.3;
+        self.regions -= 1;
+    }
+
+    fn clear(&mut self) {
+        self.freed.clear();
+        self.shelves.clear();
+        self.next_y = 1;
+        self.used = 0;
+        self.regions = 0;
+    }
+
+    fn get_stats(&self) -> PackerStats {
+        PackerStats {
+            total_area: (self.width - 2) * (self.height - 2),
+            used_area: self.used,
+            regions: self.regions,
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use std::usize;
+use super::{Packer, PackerStats};
+use super::free_list::FreeList;
+
+/// Skyline bottom-left packer. Keeps track of the top edge of the packed regions as a list of
+/// `(x, y, width)` segments.
+pub struct SkylinePacker {
+    width: usize,
+    height: usize,
+    skyline: Vec<(usize, usize, usize)>,
+    /// Space of freed regions, it is used before the rest of the area.
+    freed: FreeList,
+    used: usize,
+    regions: usize,
+}
+
+impl SkylinePacker {
+    pub fn new(width: usize, height: usize) -> Self {
+        let mut result = SkylinePacker {
+            width: width,
+            height: height,
+            skyline: Vec::new(),
+            freed: FreeList::new(),
+            used: 0,
+            regions: 0,
+        };
+        result.skyline.push((1, 1, width - 2));
+        result
+    }
+
+    fn fit(&self, index: usize, width: usize, height: usize) -> Option<usize> {
+        let (x, mut y, _) = self.skyline[index];
+        let mut width_left: i32 = width as i32;
+        let mut i = index;
+
+        if x + width > self.width - 1 {
+            return None;
+        }
+        while width_left > 0 {
+            let (_, cur_y, cur_width) = self.skyline[i];
+            if cur_y > y {
+                y = cur_y;
+            }
+
+            if y + height > self.height - 1 {
+                return None;
+            }
+
+            width_left -= cur_width as i32;
+            i += 1;
+        }
+        Some(y)
+    }
+
+    fn merge(&mut self) {
+        let mut to_remove = Vec::<usize>::new();
+        let mut last = 0;
+
+        for i in 1..self.skyline.len() {
+            if self.skyline[i].1 == self.skyline[last].1 {
+                self.skyline[last].2 += self.skyline[i].2;
+                to_remove.push(i);
+            }
+            else {
+                last = i;
+            }
+        }
+
+        for i in to_remove.iter().rev() {
+            self.skyline.remove(*i);
+        }
+    }
+
+    pub fn print_skyline(&self) {
+        println!("{:?}", self.skyline);
+    }
+}
+
+impl Packer for SkylinePacker {
+    fn get_region(&mut self, width: usize, height: usize) -> Option<(usize, usize, usize, usize)> {
+        if let Some(region) = self.freed.take(width, height) {
+            self.used += width * height;
+            self.regions += 1;
+            return Some(region);
+        }
+
+        let mut region = (0, 0, width, height);
+        let (mut best_height, mut best_width, mut best_index) = (usize::MAX, usize::MAX, usize::MAX);
+
+        let mut i = 0;
+        for &(cur_x, _, cur_width) in &self.skyline {
+            if let Some(y) = self.fit(i, width, height) {
+                if (y + height < best_height) || (y + height == best_height && cur_width < best_width) {
+                    best_height = y + height;
+                    best_index = i;
+                    best_width = cur_width;
+                    region.0 = cur_x;
+                    region.1 = y;
+                }
+            }
+            i += 1;
+        }
+
+        if best_index == usize::MAX {
+            return None;
+        }
+
+        self.skyline.insert(best_index, (region.0, region.1 + height, width));
+
+        let mut to_remove = Vec::<usize>::new();
+
+        for i in best_index + 1..self.skyline.len() {
+            if self.skyline[i].0 < region.0 + region.2 {
+                let shrink = region.0 + region.2 - self.skyline[i].0;
+                if shrink >= self.skyline[i].2 {
+                    to_remove.push(i);
+                }
+                else {
+                    self.skyline[i].0 += shrink;
+                    self.skyline[i].2 -= shrink;
+                    break;
+                }
+            }
+            else {
+                break;
+            }
+        }
+
+        for i in to_remove.iter().rev() {
+            self.skyline.remove(*i);
+        }
+
+        self.merge();
+        self.used += width * height;
+        self.regions += 1;
+        Some(region)
+    }
+
+    fn free_region(&mut self, region: (usize, usize, usize, usize)) {
+        self.freed.add(region);
+        self.used -= region.2 * region.3;
+        self.regions -= 1;
+    }
+
+    fn clear(&mut self) {
+        self.freed.clear();
+        self.skyline.clear();
+        self.skyline.push((1, 1, self.width - 2));
+        self.used = 0;
+        self.regions = 0;
+    }
+
+    fn get_stats(&self) -> PackerStats {
+        PackerStats {
+            total_area: (self.width - 2) * (self.height - 2),
+            used_area: self.used,
+            regions: self.regions,
+        }
+    }
+}
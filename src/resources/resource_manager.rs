@@ -1,21 +1,39 @@
 use std::hash::{Hash, SipHasher, Hasher};
 use std::collections::HashMap;
+use std::cmp;
+use std::mem;
+use std::usize;
 use std::rc::{Rc, Weak};
 use std::cell::RefCell;
 use super::texture_atlas::TextureAtlas;
+use super::packing::{PackingStrategy, PackerStats};
+use super::baked_atlas::BakedAtlas;
+use super::asset_source::{AssetSource, DirectorySource};
+use super::async_loader::AsyncLoader;
+use super::bitmap_font::BitmapFont;
+use super::glyph_cache::GlyphCache;
+use super::slots::Slots;
+use super::sprite_sheet::SheetLayout;
 use image;
 use image::GenericImage;
-use std::path::Path;
+use std::io;
+use std::time::SystemTime;
 use std::result;
 use std::fmt;
 use std::error::Error;
 use glium::texture::TextureCreationError;
-use rusttype::{FontCollection, Pixels, point, PositionedGlyph};
 
 pub type Result<T> = result::Result<T, ResourceManagerError>;
 
 const ATLAS_DIMENSION: usize = 2048;
 const FONT_ATLAS_DIMENSION: usize = 1024;
+const GLYPH_CACHE_PAGES: usize = 4;
+const ATLAS_GUTTER: usize = 2;
+const ASYNC_WORKER_COUNT: usize = 2;
+const MISSING_TEXTURE_NAME: &'static str = "<missing>";
+/// Magenta and black checkerboard that stale texture handles resolve to.
+const MISSING_TEXTURE_PIXELS: [u8; 16] = [255, 0, 255, 255, 0, 0, 0, 255,
+                                          0, 0, 0, 255, 255, 0, 255, 255];
 
 #[derive(Clone)]
 pub struct StringId {
@@ -49,22 +67,316 @@ impl StringId {
     }
 }
 
-struct TextureInternal {
-    atlas: Weak<RefCell<TextureAtlas>>,
+/// Load state of a texture, only textures created by `ResourceManager::create_texture_async`
+/// are ever `Loading` or `Failed`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadState {
+    Loading,
+    Loaded,
+    Failed,
+}
+
+#[derive(Clone)]
+struct TextureRegion {
+    atlas: Rc<RefCell<TextureAtlas>>,
+    position: (usize, usize),
+    size: (u32, u32),
     uv_min: (f32, f32),
     uv_max: (f32, f32),
     pixel_dimension: f32,
+    insets: (f32, f32, f32, f32),
+    state: LoadState,
+    /// The image is stored turned 90° clockwise in the atlas to pack it more tightly, `size` is
+    /// still the size of the upright image.
+    rotated: bool,
+    /// Pixels per logical unit, e.g. 2 for images loaded from an `@2x` variant.
+    scale: f32,
 }
 
+impl TextureRegion {
+    fn new(atlas: Rc<RefCell<TextureAtlas>>, x: usize, y: usize, width: u32, height: u32) -> TextureRegion {
+        let mut result = TextureRegion {
+            atlas: atlas,
+            position: (0, 0),
+            size: (0, 0),
+            uv_min: (0.0, 0.0),
+            uv_max: (0.0, 0.0),
+            pixel_dimension: 0.0,
+            insets: (0.0, 0.0, 0.0, 0.0),
+            state: LoadState::Loaded,
+            rotated: false,
+            scale: 1.0,
+        };
+        let atlas = result.atlas.clone();
+        result.move_to(atlas, x, y, width, height);
+        result
+    }
+
+    /// `width` and `height` are the size of the upright image, the space it takes up in the atlas
+    /// is swapped if the region is rotated.
+    fn move_to(&mut self, atlas: Rc<RefCell<TextureAtlas>>, x: usize, y: usize, width: u32, height: u32) {
+        self.size = (width, height);
+        let (footprint_width, footprint_height) = self.get_footprint();
+        let (atlas_width, atlas_height) = atlas.borrow().get_dimensions();
+        self.pixel_dimension = 1.0f32 / atlas_width as f32;
+        self.uv_min = (x as f32 / atlas_width as f32, y as f32 / atlas_height as f32);
+        self.uv_max = ((x as f32 + footprint_width as f32) / atlas_width as f32,
+                       (y as f32 + footprint_height as f32) / atlas_height as f32);
+        self.position = (x, y);
+        self.atlas = atlas;
+    }
+
+    /// Size of the region in the atlas.
+    fn get_footprint(&self) -> (u32, u32) {
+        if self.rotated { (self.size.1, self.size.0) } else { self.size }
+    }
+
+    /// Copies the RGBA pixels of the upright image into the atlas.
+    fn write_pixels(&self, pixels: &[u8]) {
+        let (width, height) = self.size;
+        let mut atlas = self.atlas.borrow_mut();
+        if self.rotated {
+            let rotated = rotate_pixels(pixels, width as usize, height as usize);
+            atlas.set_region(self.position.0, self.position.1, height as usize, width as usize, &rotated, height as usize * 4);
+        } else {
+            atlas.set_region(self.position.0, self.position.1, width as usize, height as usize, pixels, width as usize * 4);
+        }
+    }
+}
+
+/// Turns RGBA pixels 90° clockwise, the result is `height` pixels wide.
+fn rotate_pixels(pixels: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let mut result = vec![0; pixels.len()];
+    for y in 0..width {
+        for x in 0..height {
+            let source = ((height - 1 - x) * width + y) * 4;
+            let target = (y * height + x) * 4;
+            result[target..target + 4].copy_from_slice(&pixels[source..source + 4]);
+        }
+    }
+    result
+}
+
+struct TextureInternal {
+    name: StringId,
+    region: Rc<RefCell<TextureRegion>>,
+    /// Part of the region that sub textures show (x, y, width, height) in pixels of the upright
+    /// image, `None` for the whole region.
+    rect: Option<(u32, u32, u32, u32)>,
+    /// File the texture was loaded from, i.e. the variant for the scale factor at that time,
+    /// and its modification time. `None` for textures that aren't hot-reloaded.
+    source: Option<(String, SystemTime)>,
+}
+
+/// Flips and clockwise quarter turns that a texture is shown with. The flips are applied
+/// before the rotation.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct TextureTransform {
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+    pub quarter_turns: u8,
+}
+
+impl TextureTransform {
+    /// Maps a point of the shown texture to the upright image, both in the range 0 to 1 with
+    /// the origin at the top left.
+    fn to_source(&self, s: f32, t: f32) -> (f32, f32) {
+        let (mut u, mut v) = (s, t);
+        for _ in 0..self.quarter_turns % 4 {
+            let turned = (v, 1.0 - u);
+            u = turned.0;
+            v = turned.1;
+        }
+        if self.flip_horizontal {
+            u = 1.0 - u;
+        }
+        if self.flip_vertical {
+            v = 1.0 - v;
+        }
+        (u, v)
+    }
+
+    fn is_turned_sideways(&self) -> bool {
+        self.quarter_turns % 2 == 1
+    }
+}
+
+/// Handle to a texture of a `ResourceManager`. Handles stay valid until the texture is unloaded
+/// with `ResourceManager::unload_texture`, after that they resolve to a placeholder texture.
+/// A handle also carries the flips and rotation the texture is shown with.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct TextureHandle {
+    index: u32,
+    generation: u32,
+    transform: TextureTransform,
+}
+
+impl TextureHandle {
+    /// Mirrors the texture as it is currently shown from left to right.
+    pub fn flip_horizontal(self) -> TextureHandle {
+        let mut result = self;
+        result.transform.flip_horizontal = !self.transform.flip_horizontal;
+        result.transform.quarter_turns = (4 - self.transform.quarter_turns % 4) % 4;
+        result
+    }
+
+    /// Mirrors the texture as it is currently shown from top to bottom.
+    pub fn flip_vertical(self) -> TextureHandle {
+        let mut result = self;
+        result.transform.flip_vertical = !self.transform.flip_vertical;
+        result.transform.quarter_turns = (4 - self.transform.quarter_turns % 4) % 4;
+        result
+    }
+
+    /// Turns the texture by 90° clockwise.
+    pub fn rotate_clockwise(self) -> TextureHandle {
+        let mut result = self;
+        result.transform.quarter_turns = (self.transform.quarter_turns + 1) % 4;
+        result
+    }
+
+    /// Turns the texture by 90° counter-clockwise.
+    pub fn rotate_counter_clockwise(self) -> TextureHandle {
+        let mut result = self;
+        result.transform.quarter_turns = (self.transform.quarter_turns + 3) % 4;
+        result
+    }
+
+    pub fn get_transform(&self) -> TextureTransform {
+        self.transform
+    }
+
+    /// Returns a handle to the same texture that is shown with `transform`.
+    pub fn with_transform(self, transform: TextureTransform) -> TextureHandle {
+        let mut result = self;
+        result.transform = transform;
+        result
+    }
+
+    /// Whether both handles refer to the same texture, regardless of their flips and rotation.
+    pub fn is_same_texture(&self, other: &TextureHandle) -> bool {
+        self.index == other.index && self.generation == other.generation
+    }
+}
+
+/// Handle to a font of a `ResourceManager`. Handles stay valid until the font is unloaded with
+/// `ResourceManager::unload_font`, after that they don't resolve anymore.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct FontHandle {
+    index: u32,
+    generation: u32,
+}
+
+/// A resolved `TextureHandle`, i.e. an image inside a texture atlas. It is only meant to be kept
+/// while building a draw batch, the image can be moved to another atlas by
+/// `ResourceManager::compact_atlases`.
 #[derive(Clone)]
 pub struct Texture {
     pub name: StringId,
-    pub atlas: Rc<RefCell<TextureAtlas>>,
-    pub uv_min: (f32, f32),
-    pub uv_max: (f32, f32),
-    pub pixel_dimension: f32,
+    region: Rc<RefCell<TextureRegion>>,
+    rect: Option<(u32, u32, u32, u32)>,
+    transform: TextureTransform,
+}
+
+impl Texture {
+    pub fn get_atlas(&self) -> Rc<RefCell<TextureAtlas>> {
+        self.region.borrow().atlas.clone()
+    }
+
+    /// Returns the atlas UV of the point `s`, `t` of the texture as it is shown, i.e. with
+    /// flips and rotation applied. `(0, 0)` is the top left and `(1, 1)` the bottom right corner.
+    pub fn get_uv(&self, s: f32, t: f32) -> (f32, f32) {
+        let (u, v) = self.transform.to_source(s, t);
+        let region = self.region.borrow();
+        let (rect_x, rect_y, rect_width, rect_height) = self.rect.unwrap_or((0, 0, region.size.0, region.size.1));
+        let (image_x, image_y) = (rect_x as f32 + u * rect_width as f32, rect_y as f32 + v * rect_height as f32);
+        let (x, y) = if region.rotated {
+            (region.size.1 as f32 - image_y, image_x)
+        } else {
+            (image_x, image_y)
+        };
+        let (atlas_width, atlas_height) = region.atlas.borrow().get_dimensions();
+        ((region.position.0 as f32 + x) / atlas_width as f32, (region.position.1 as f32 + y) / atlas_height as f32)
+    }
+
+    /// Smallest UVs of the texture in the atlas. With flips or rotation these don't have to
+    /// belong to the top left corner, use `get_uv` for the corners.
+    pub fn get_uv_min(&self) -> (f32, f32) {
+        let (a, b) = (self.get_uv(0.0, 0.0), self.get_uv(1.0, 1.0));
+        (a.0.min(b.0), a.1.min(b.1))
+    }
+
+    /// Largest UVs of the texture in the atlas.
+    pub fn get_uv_max(&self) -> (f32, f32) {
+        let (a, b) = (self.get_uv(0.0, 0.0), self.get_uv(1.0, 1.0));
+        (a.0.max(b.0), a.1.max(b.1))
+    }
+
+    pub fn get_pixel_dimension(&self) -> f32 {
+        self.region.borrow().pixel_dimension
+    }
+
+    /// Pixels per logical unit, higher than 1 for textures that were loaded from a HiDPI variant,
+    /// see `ResourceManager::set_scale_factor`.
+    pub fn get_scale(&self) -> f32 {
+        self.region.borrow().scale
+    }
+
+    /// Size in logical units as the texture is shown, i.e. the size in pixels divided by the scale.
+    /// This is the size to lay the texture out with.
+    pub fn get_logical_size(&self) -> (f32, f32) {
+        let (width, height) = self.get_size();
+        let scale = self.get_scale();
+        (width as f32 / scale, height as f32 / scale)
+    }
+
+    /// Size in pixels as the texture is shown, width and height are swapped by quarter turns.
+    pub fn get_size(&self) -> (u32, u32) {
+        let size = match self.rect {
+            Some((_, _, width, height)) => (width, height),
+            None => self.region.borrow().size,
+        };
+        if self.transform.is_turned_sideways() { (size.1, size.0) } else { size }
+    }
+
+    pub fn get_transform(&self) -> TextureTransform {
+        self.transform
+    }
+
+    /// Nine-slice insets (left, right, top, bottom) in pixels, only set for baked textures that
+    /// aren't sub textures. Flips and rotation move them to the matching sides. Divide them by
+    /// `get_scale` for logical units.
+    pub fn get_insets(&self) -> (f32, f32, f32, f32) {
+        if self.rect.is_some() {
+            return (0.0, 0.0, 0.0, 0.0);
+        }
+        let (mut left, mut right, mut top, mut bottom) = self.region.borrow().insets;
+        if self.transform.flip_horizontal {
+            let flipped = (right, left);
+            left = flipped.0;
+            right = flipped.1;
+        }
+        if self.transform.flip_vertical {
+            let flipped = (bottom, top);
+            top = flipped.0;
+            bottom = flipped.1;
+        }
+        for _ in 0..self.transform.quarter_turns % 4 {
+            let turned = (bottom, top, left, right);
+            left = turned.0;
+            right = turned.1;
+            top = turned.2;
+            bottom = turned.3;
+        }
+        (left, right, top, bottom)
+    }
+
+    pub fn get_load_state(&self) -> LoadState {
+        self.region.borrow().state
+    }
 }
 
+#[derive(Clone, Copy)]
 pub struct Glyph {
     pub uv_min: (f32, f32),
     pub uv_max: (f32, f32),
@@ -72,6 +384,10 @@ pub struct Glyph {
     pub height: f32,
     pub offset: (f32, f32),
     pub advance_x: f32,
+    /// Id of the face the glyph was taken from, this can be one of the fallbacks of a font.
+    pub face: usize,
+    /// Glyph cache page the glyph is stored on, see `Font::get_page`.
+    pub page: usize,
 }
 
 impl Glyph {
@@ -83,116 +399,211 @@ impl Glyph {
             height: 0.0,
             offset: (0.0, 0.0),
             advance_x: 0.0,
+            face: 0,
+            page: 0,
         }
     }
 }
 
+/// Vertical metrics of a font in pixels. `ascent`, `x_height` and `cap_height` are measured
+/// upwards from the baseline, `descent` is negative for fonts that go below the baseline.
+#[derive(Clone, Copy, Debug)]
+pub struct FontMetrics {
+    pub ascent: f32,
+    pub descent: f32,
+    pub line_gap: f32,
+    /// Distance between the baselines of two lines.
+    pub line_height: f32,
+    pub x_height: f32,
+    pub cap_height: f32,
+}
+
+impl FontMetrics {
+    pub fn new() -> FontMetrics {
+        FontMetrics {
+            ascent: 0.0,
+            descent: 0.0,
+            line_gap: 0.0,
+            line_height: 0.0,
+            x_height: 0.0,
+            cap_height: 0.0,
+        }
+    }
+
+    /// Height of a block of `text` from the ascent of the first line to the descent of the last.
+    /// Lines are separated by `\n`.
+    pub fn get_text_height(&self, text: &str) -> f32 {
+        let lines = text.split('\n').count();
+        self.ascent - self.descent + (lines - 1) as f32 * self.line_height
+    }
+
+    /// Distance from the top of a box of `height` down to the baseline that centres a line of
+    /// text vertically in it.
+    pub fn get_centered_baseline(&self, height: f32) -> f32 {
+        (height + self.ascent + self.descent) / 2.0
+    }
+}
+
+/// How the glyphs of a font are stored in its atlas.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum GlyphMode {
+    /// Antialiased coverage at exactly the font size.
+    Coverage,
+    /// Signed distance fields that stay sharp when the text is scaled. `spread` is the distance
+    /// in pixels around the glyph outline that is stored, it limits how wide outlines and glows
+    /// can get.
+    DistanceField { spread: usize },
+    /// Glyphs copied from the pages of a bitmap font, characters it doesn't have can only come
+    /// from fallbacks.
+    Bitmap,
+}
+
 struct FontInternal {
-    atlas: Weak<RefCell<TextureAtlas>>,
-    glyphs: Weak<RefCell<HashMap<char, Glyph>>>,
-    kernings: Weak<RefCell<HashMap<(char, char), f32>>>,
+    name: StringId,
+    path: String,
+    size: f32,
+    mode: GlyphMode,
+    metrics: FontMetrics,
+    face: usize,
+    fallbacks: Vec<usize>,
+    modified: Option<SystemTime>,
 }
 
+/// A resolved `FontHandle`, i.e. a font face at one size. The glyphs of all fonts live in the
+/// glyph cache of the `ResourceManager`, so fonts of different sizes share their atlas pages.
 #[derive(Clone)]
 pub struct Font {
     pub name: StringId,
     pub path: String,
-    pub atlas: Rc<RefCell<TextureAtlas>>,
-    pub glyphs: Rc<RefCell<HashMap<char, Glyph>>>,
-    pub kernings: Rc<RefCell<HashMap<(char, char), f32>>>,
     pub size: f32,
+    pub mode: GlyphMode,
+    pub metrics: FontMetrics,
+    face: usize,
+    /// Faces that are searched in order for characters this face doesn't have.
+    fallbacks: Vec<usize>,
+    glyph_cache: Rc<RefCell<GlyphCache>>,
 }
 
 impl Font {
-    pub fn load_glyphs(&self) {
-        use std::path::Path;
-        use std::fs::File;
-        use std::io::prelude::*;
-        use std::f32;
-
-        let path = Path::new(&self.path);
-        let display = path.display();
-
-        let mut file = match File::open(&path) {
-            Err(err) => panic!("couldn't open {}: {}", display, Error::description(&err)),
-            Ok(file) => file,
-        };
+    /// Rasterizes the glyphs of the characters `first` to `last` (inclusive) up front. This is
+    /// optional, `Text` rasterizes missing glyphs the first time it lays them out.
+    pub fn load_glyph_range(&self, first: char, last: char) -> Result<()> {
+        let chars = (first as u32..last as u32 + 1).filter_map(|c| ::std::char::from_u32(c));
+        self.load_glyphs(chars)
+    }
 
-        let mut font_data = Vec::new();
-        match file.read_to_end(&mut font_data) {
-            Err(err) => panic!("couldn't read {}: {}", display, Error::description(&err)),
-            Ok(_) => {}
-        };
+    /// Rasterizes the glyphs of all characters that aren't in the glyph cache yet, the atlas
+    /// pages are then uploaded with the next draw batch. Stops at the first glyph that doesn't
+    /// fit into a glyph cache page.
+    pub fn load_glyphs<I: IntoIterator<Item = char>>(&self, chars: I) -> Result<()> {
+        for c in chars {
+            try!(self.get_glyph(c));
+        }
+        Ok(())
+    }
 
-        let collection = FontCollection::from_bytes(font_data);
-        let font = collection.into_font().unwrap();
+    /// Returns the glyph for `c` from the first face that has it, rasterizing it first if
+    /// necessary. Fails with `ResourceManagerError::TooLarge` if the glyph doesn't fit into a
+    /// glyph cache page.
+    pub fn get_glyph(&self, c: char) -> Result<Option<Glyph>> {
+        let mut faces = vec![self.face];
+        faces.extend(self.fallbacks.iter().cloned());
+        self.glyph_cache.borrow_mut().get_glyph(&faces, self.size, self.mode, c)
+    }
 
-        let scale = Pixels(self.size);
+    /// Kerning between two glyphs as returned by `get_glyph`. Glyphs from different faces aren't
+    /// kerned.
+    pub fn get_kerning(&self, first: (char, &Glyph), second: (char, &Glyph)) -> f32 {
+        if first.1.face != second.1.face {
+            return 0.0;
+        }
+        self.glyph_cache.borrow_mut().get_kerning(first.1.face, self.size, first.0, second.0)
+    }
 
-        for i in 0..129 {
-            let c = (i as u8) as char;
-            let g = match font.glyph(c) {
-                Some(g) => g,
-                None => continue,
-            };
-            let g = g.scaled(scale);
-            if let Some(exact_bounding_box) = g.exact_bounding_box() {
-                let pos_x: f32 = -(exact_bounding_box.min.x as f32).floor();
-                let pos_y: f32 = (exact_bounding_box.max.y as f32).ceil();
-                let g = g.positioned(point(pos_x, pos_y));
-                let pixel_bounding_box = match g.pixel_bounding_box() {
-                    Some(pixel_bounding_box) => pixel_bounding_box,
-                    None => continue,
-                };
-                let width = pixel_bounding_box.max.x as usize;
-                let height = pixel_bounding_box.max.y as usize;
-                let mut buf: Vec<u8> = vec![0; (width + 1) * (height + 1)];
-                g.draw(|x, y, v| {
-                    buf[x as usize + y as usize * width] = (v * 255.0) as u8;
-                });
-                let mut atlas = self.atlas.borrow_mut();
-                let region = match atlas.get_region(width, height) {
-                    Some(region) => region,
-                    None => continue,
-                };
-                atlas.set_region(region.0, region.1, region.2, region.3, &buf, width as usize);
+    /// Atlas of a glyph cache page and the mode of the glyphs on it.
+    pub fn get_page(&self, page: usize) -> (Rc<RefCell<TextureAtlas>>, GlyphMode) {
+        self.glyph_cache.borrow().get_page(page)
+    }
+}
 
-                let mut glyph = Glyph::new();
-                glyph.uv_min = (region.0 as f32 / FONT_ATLAS_DIMENSION as f32, region.1 as f32 / FONT_ATLAS_DIMENSION as f32);
-                glyph.uv_max = ((region.0 as f32 + width as f32) / FONT_ATLAS_DIMENSION as f32, (region.1 as f32 + height as f32)  / FONT_ATLAS_DIMENSION as f32);
-                glyph.width = region.2 as f32;
-                glyph.height = region.3 as f32;
-                glyph.offset = (-pos_x, pos_y);
-                glyph.advance_x = g.h_metrics().advance_width;
-                self.glyphs.borrow_mut().insert(c, glyph);
-            } else {
-                // for whitespace characters
-                let mut glyph = Glyph::new();
-                glyph.advance_x = g.h_metrics().advance_width;
-                self.glyphs.borrow_mut().insert(c, glyph);
-            }
+/// A frame of a sprite sheet, `duration` is in seconds. Frames whose transparent borders were
+/// trimmed on export keep their place within the untrimmed sprite, `offset` and `source_size`
+/// are in logical units.
+#[derive(Clone, Copy, Debug)]
+pub struct SpriteFrame {
+    pub texture: TextureHandle,
+    pub offset: (f32, f32),
+    pub source_size: (f32, f32),
+    pub duration: f32,
+}
 
-            for c_other in self.glyphs.borrow().keys() {
-                let kerning1 = font.pair_kerning(scale, c, *c_other);
-                let kerning2 = font.pair_kerning(scale, *c_other, c);
-                if kerning1 != 0.0 {
-                    self.kernings.borrow_mut().insert((c, *c_other), kerning1);
-                }
-                if kerning2 != 0.0 {
-                    self.kernings.borrow_mut().insert((*c_other, c), kerning2);
-                }
-            }
-        }
-        let mut atlas = self.atlas.borrow_mut();
-        atlas.save_to_png(0);
+/// Frames of a sprite sheet as returned by `ResourceManager::create_sprite_sheet`. Every frame is
+/// a texture of its own named `<sheet>#<frame name>`, the frames of a grid are named by their
+/// index.
+#[derive(Clone, Debug)]
+pub struct SpriteSheet {
+    pub frames: Vec<SpriteFrame>,
+    /// Animations as inclusive ranges of frames, e.g. the frame tags of an Aseprite export.
+    pub tags: HashMap<String, (usize, usize)>,
+}
+
+impl SpriteSheet {
+    /// Frames of the animation `tag`.
+    pub fn get_animation(&self, tag: &str) -> Option<&[SpriteFrame]> {
+        self.tags.get(tag).map(|&(from, to)| &self.frames[from..to + 1])
     }
 }
 
+/// Kind of resource an atlas belongs to, memory budgets are set per category.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ResourceCategory {
+    /// Texture atlases of images, including baked atlases.
+    Textures,
+    /// Glyph cache pages.
+    Fonts,
+}
+
+/// Memory use of an atlas as returned by `ResourceManager::get_atlas_stats`.
+pub struct AtlasStats {
+    pub category: ResourceCategory,
+    pub atlas: Rc<RefCell<TextureAtlas>>,
+    pub dimensions: (usize, usize),
+    pub cpu_bytes: usize,
+    /// 0 for atlases that were never uploaded.
+    pub gpu_bytes: usize,
+    pub packer: PackerStats,
+}
+
+/// Description of a loaded texture as returned by `ResourceManager::get_texture_infos`.
+pub struct TextureInfo {
+    pub name: StringId,
+    pub atlas: Rc<RefCell<TextureAtlas>>,
+    pub uv_min: (f32, f32),
+    pub uv_max: (f32, f32),
+    pub size: (u32, u32),
+}
+
+/// Description of a loaded font as returned by `ResourceManager::get_font_infos`.
+pub struct FontInfo {
+    pub name: StringId,
+    pub path: String,
+    pub size: f32,
+    /// Glyph cache pages that contain glyphs of the font.
+    pub atlases: Vec<Rc<RefCell<TextureAtlas>>>,
+    pub glyph_count: usize,
+}
+
 #[derive(Debug)]
 pub enum ResourceManagerError {
     Image(image::ImageError),
     Texture(TextureCreationError),
+    Io(io::Error),
+    InvalidManifest(String),
+    /// The font file couldn't be parsed, contains the name of the font.
+    InvalidFont(String),
     TooLarge,
+    /// Another atlas would exceed the memory budget of its category.
+    OverBudget,
 }
 
 impl fmt::Display for ResourceManagerError {
@@ -200,7 +611,11 @@ impl fmt::Display for ResourceManagerError {
         match *self {
             ResourceManagerError::Image(ref err) => write!(f, "Image error: {}", err),
             ResourceManagerError::Texture(_) => write!(f, "Texture creation error!"),
+            ResourceManagerError::Io(ref err) => write!(f, "IO error: {}", err),
+            ResourceManagerError::InvalidManifest(ref err) => write!(f, "Invalid manifest: {}", err),
+            ResourceManagerError::InvalidFont(ref name) => write!(f, "Invalid font: {}", name),
             ResourceManagerError::TooLarge => write!(f, "TooLarge error: image dimension too large!"),
+            ResourceManagerError::OverBudget => write!(f, "OverBudget error: memory budget exceeded!"),
         }
     }
 }
@@ -210,7 +625,11 @@ impl Error for ResourceManagerError {
         match *self {
             ResourceManagerError::Image(ref err) => err.description(),
             ResourceManagerError::Texture(_) => &"Texture creation Error",
+            ResourceManagerError::Io(ref err) => err.description(),
+            ResourceManagerError::InvalidManifest(_) => &"Invalid atlas manifest",
+            ResourceManagerError::InvalidFont(_) => &"Invalid font file",
             ResourceManagerError::TooLarge => &"Image dimension too large!",
+            ResourceManagerError::OverBudget => &"Memory budget exceeded!",
         }
     }
 
@@ -218,6 +637,7 @@ impl Error for ResourceManagerError {
         match *self {
             ResourceManagerError::Image(ref err) => Some(err),
             ResourceManagerError::Texture(_) => None,
+            ResourceManagerError::Io(ref err) => Some(err),
             _ => None,
         }
     }
@@ -229,144 +649,1149 @@ impl From<image::ImageError> for ResourceManagerError {
     }
 }
 
+impl From<io::Error> for ResourceManagerError {
+    fn from(err: io::Error) -> ResourceManagerError {
+        ResourceManagerError::Io(err)
+    }
+}
+
 impl From<TextureCreationError> for ResourceManagerError {
     fn from(err: TextureCreationError) -> ResourceManagerError {
         ResourceManagerError::Texture(err)
     }
 }
 
+/// Name a font is registered under, every size and glyph mode of a font file is a separate font.
+fn font_key(string: &str, size: u32, mode: GlyphMode) -> String {
+    match mode {
+        GlyphMode::Coverage => format!("{}{}", string, size),
+        GlyphMode::DistanceField { spread } => format!("{}{}sdf{}", string, size, spread),
+        GlyphMode::Bitmap => string.to_string(),
+    }
+}
+
+/// Gives the atlas space of a texture back. Textures that are loading or failed to load only show
+/// the shared placeholder, they don't own any space.
+fn free_region(region: &TextureRegion) {
+    if region.state == LoadState::Loaded {
+        region.atlas.borrow_mut().free_region(region.position.0, region.position.1);
+    }
+}
+
+/// Tries to place an image in `atlas`, turned sideways if it only fits that way.
+fn place_region(atlas: &Rc<RefCell<TextureAtlas>>, width: u32, height: u32) -> Option<(usize, usize, bool)> {
+    let mut atlas = atlas.borrow_mut();
+    if let Some(region) = atlas.get_region(width as usize, height as usize) {
+        return Some((region.0, region.1, false));
+    }
+    if width != height {
+        if let Some(region) = atlas.get_region(height as usize, width as usize) {
+            return Some((region.0, region.1, true));
+        }
+    }
+    None
+}
+
+fn create_atlas(strategy: PackingStrategy, mip_levels: u32) -> Rc<RefCell<TextureAtlas>> {
+    let mut atlas = TextureAtlas::new_with_strategy(ATLAS_DIMENSION, ATLAS_DIMENSION, 4, strategy);
+    atlas.set_gutter(get_atlas_gutter(mip_levels));
+    atlas.set_mipmaps(mip_levels > 0);
+    Rc::new(RefCell::new(atlas))
+}
+
+/// Gutter that allows `mip_levels` levels below the full resolution, see
+/// `TextureAtlas::set_mipmaps`.
+fn get_atlas_gutter(mip_levels: u32) -> usize {
+    if mip_levels > 0 { cmp::max(ATLAS_GUTTER, 1 << mip_levels) } else { ATLAS_GUTTER }
+}
+
 pub struct ResourceManager {
     string_id_table: Rc<RefCell<HashMap<u64, String>>>,
+    asset_sources: Vec<Box<AssetSource>>,
+    hot_reload: bool,
+    packing_strategy: PackingStrategy,
+    mip_levels: u32,
     current_atlas: Rc<RefCell<TextureAtlas>>,
-    textures: HashMap<u64, TextureInternal>,
-    fonts: HashMap<u64, FontInternal>,
+    /// Texture atlases that were filled before the current one, the space of textures that were
+    /// unloaded from them is reused before a new atlas is started.
+    older_atlases: Vec<Weak<RefCell<TextureAtlas>>>,
+    textures: HashMap<u64, TextureHandle>,
+    texture_slots: Slots<TextureInternal>,
+    missing_texture: Texture,
+    async_loader: Option<AsyncLoader>,
+    pending_textures: HashMap<u64, TextureHandle>,
+    placeholder: Option<Rc<RefCell<TextureRegion>>>,
+    texture_budget: Option<usize>,
+    scale_factor: f32,
+    glyph_cache: Rc<RefCell<GlyphCache>>,
+    fonts: HashMap<u64, FontHandle>,
+    font_slots: Slots<FontInternal>,
 }
 
 impl ResourceManager {
     pub fn new() -> Self {
+        let string_id_table = Rc::new(RefCell::new(HashMap::<u64, String>::new()));
+        let current_atlas = create_atlas(PackingStrategy::Skyline, 0);
+        let missing_texture = {
+            let (x, y, _, _) = current_atlas.borrow_mut().get_region(2, 2).unwrap();
+            current_atlas.borrow_mut().set_region(x, y, 2, 2, &MISSING_TEXTURE_PIXELS, 8);
+            Texture {
+                name: StringId::new(MISSING_TEXTURE_NAME, Rc::downgrade(&string_id_table)),
+                region: Rc::new(RefCell::new(TextureRegion::new(current_atlas.clone(), x, y, 2, 2))),
+                rect: None,
+                transform: TextureTransform::default(),
+            }
+        };
         ResourceManager {
-            string_id_table: Rc::new(RefCell::new(HashMap::<u64, String>::new())),
-            current_atlas: Rc::new(RefCell::new(TextureAtlas::new(ATLAS_DIMENSION, ATLAS_DIMENSION, 4))),
+            string_id_table: string_id_table,
+            asset_sources: vec![Box::new(DirectorySource::new(".")) as Box<AssetSource>],
+            hot_reload: false,
+            packing_strategy: PackingStrategy::Skyline,
+            mip_levels: 0,
+            current_atlas: current_atlas,
+            older_atlases: Vec::new(),
             textures: HashMap::new(),
+            texture_slots: Slots::new(),
+            missing_texture: missing_texture,
+            async_loader: None,
+            pending_textures: HashMap::new(),
+            placeholder: None,
+            texture_budget: None,
+            scale_factor: 1.0,
+            glyph_cache: Rc::new(RefCell::new(GlyphCache::new(FONT_ATLAS_DIMENSION, GLYPH_CACHE_PAGES))),
             fonts: HashMap::new(),
+            font_slots: Slots::new(),
+        }
+    }
+
+    /// Adds a source that asset names are resolved through. Sources that were added later take
+    /// precedence, so they can override assets of earlier ones. A new resource manager starts
+    /// out with a `DirectorySource` for the working directory.
+    pub fn add_asset_source(&mut self, source: Box<AssetSource>) {
+        self.asset_sources.push(source);
+    }
+
+    pub fn clear_asset_sources(&mut self) {
+        self.asset_sources.clear();
+    }
+
+    fn read_asset(&self, string: &str) -> Result<Vec<u8>> {
+        for source in self.asset_sources.iter().rev() {
+            if let Some(result) = source.read(string) {
+                return result.map_err(ResourceManagerError::from);
+            }
+        }
+        Err(ResourceManagerError::Io(io::Error::new(io::ErrorKind::NotFound,
+                                                    format!("{} not found in any asset source", string))))
+    }
+
+    fn contains_asset(&self, string: &str) -> bool {
+        self.asset_sources.iter().any(|source| source.contains(string))
+    }
+
+    /// Sets the ratio of physical pixels to logical units of the display, e.g. 2 on most HiDPI
+    /// monitors. Textures, baked atlases and sprite sheets that are created from now on prefer
+    /// the `name@2x.png` variant of an asset (or `@3x` and so on, up to the scale factor rounded
+    /// up) if an asset source contains one. Textures that are already loaded keep their variant,
+    /// also when they are hot-reloaded.
+    pub fn set_scale_factor(&mut self, scale_factor: f32) {
+        self.scale_factor = scale_factor;
+    }
+
+    pub fn get_scale_factor(&self) -> f32 {
+        self.scale_factor
+    }
+
+    /// Picks the variant of the asset `string` for the scale factor and returns its name and
+    /// its pixels per logical unit. Falls back to `string` itself at a scale of 1.
+    fn find_variant(&self, string: &str) -> (String, f32) {
+        let extension = match string.rfind('.') {
+            Some(i) if string.rfind('/').map_or(true, |slash| slash < i) => i,
+            _ => string.len(),
+        };
+        let mut scale = self.scale_factor.ceil() as u32;
+        while scale > 1 {
+            let variant = format!("{}@{}x{}", &string[..extension], scale, &string[extension..]);
+            if self.contains_asset(&variant) {
+                return (variant, scale as f32);
+            }
+            scale -= 1;
+        }
+        (string.to_string(), 1.0)
+    }
+
+    fn get_asset_modified(&self, string: &str) -> Option<SystemTime> {
+        for source in self.asset_sources.iter().rev() {
+            if source.contains(string) {
+                return source.get_modified(string);
+            }
+        }
+        None
+    }
+
+    /// Enables hot-reloading, see `reload_changed_assets`.
+    pub fn set_hot_reload(&mut self, hot_reload: bool) {
+        self.hot_reload = hot_reload;
+    }
+
+    /// Polls the modification times of the files behind loaded textures and fonts and reloads the
+    /// ones that changed. Any change counts, also to an older time, e.g. after a checkout or a
+    /// restore from a backup. Existing `TextureHandle`s and `FontHandle`s see the new pixels, the affected
+    /// atlases are marked for upload and draw batches have to be rebuilt. Returns the names of the
+    /// reloaded assets and the assets that failed to reload together with the error, these keep
+    /// their old contents and are tried again on the next call.
+    pub fn reload_changed_assets(&mut self) -> (Vec<String>, Vec<(String, ResourceManagerError)>) {
+        let mut reloaded = Vec::new();
+        let mut failed = Vec::new();
+        if !self.hot_reload {
+            return (reloaded, failed);
+        }
+
+        let mut changed_textures = Vec::new();
+        for (index, generation, texture_internal) in self.texture_slots.iter() {
+            // textures from `create_texture_async` show a placeholder until they are loaded
+            if texture_internal.region.borrow().state != LoadState::Loaded {
+                continue;
+            }
+            if let Some((ref path, modified)) = texture_internal.source {
+                match self.get_asset_modified(path) {
+                    Some(current) if current != modified => changed_textures.push((index, generation, path.clone(), texture_internal.region.clone(), current)),
+                    _ => {}
+                }
+            }
+        }
+        for (index, generation, path, region, modified) in changed_textures {
+            match self.reload_texture(&path, &region) {
+                Ok(()) => {
+                    self.texture_slots.get_mut(index, generation).unwrap().source = Some((path.clone(), modified));
+                    reloaded.push(path);
+                }
+                Err(err) => failed.push((path, err)),
+            }
+        }
+
+        // fonts of different sizes share their face, so faces are reloaded instead of fonts
+        let mut changed_faces: Vec<(usize, String, SystemTime)> = Vec::new();
+        for (_, _, font_internal) in self.font_slots.iter() {
+            if let Some(modified) = font_internal.modified {
+                if changed_faces.iter().any(|changed| changed.0 == font_internal.face) {
+                    continue;
+                }
+                match self.get_asset_modified(&font_internal.path) {
+                    Some(current) if current != modified => changed_faces.push((font_internal.face, font_internal.path.clone(), current)),
+                    _ => {}
+                }
+            }
+        }
+        for (face, path, modified) in changed_faces {
+            let data = match self.read_asset(&path) {
+                Ok(data) => data,
+                Err(err) => {
+                    failed.push((path, err));
+                    continue;
+                }
+            };
+            // the old face is kept if the new file doesn't parse, it might still be in the middle
+            // of being written
+            if let Err(err) = self.glyph_cache.borrow_mut().replace_face(face, data) {
+                failed.push((path, err));
+                continue;
+            }
+            let glyph_cache = self.glyph_cache.borrow();
+            for font_internal in self.font_slots.values_mut().filter(|font_internal| font_internal.face == face) {
+                font_internal.modified = Some(modified);
+                // `Text` reads the metrics whenever it is measured or batched, so it follows the reload
+                font_internal.metrics = glyph_cache.get_metrics(face, font_internal.size);
+            }
+            reloaded.push(path);
+        }
+        (reloaded, failed)
+    }
+
+    /// Reads the image of a texture again from `path`, the variant it was loaded from, so it
+    /// keeps its scale.
+    fn reload_texture(&mut self, path: &str, region: &Rc<RefCell<TextureRegion>>) -> Result<()> {
+        let data = try!(self.read_asset(path));
+        let img = try!(image::load_from_memory(&data));
+        let (width, height) = img.dimensions();
+        let pixels = img.to_rgba().into_raw();
+
+        if region.borrow().size != (width, height) {
+            let (atlas, x, y, rotated) = try!(self.allocate_region(width, height));
+            {
+                let mut region = region.borrow_mut();
+                free_region(&region);
+                region.rotated = rotated;
+                region.move_to(atlas, x, y, width, height);
+            }
+            // sub textures keep their rect, but it must not reach outside of the new image
+            for texture_internal in self.texture_slots.values_mut() {
+                let is_shared = &(*texture_internal.region) as *const RefCell<TextureRegion> ==
+                                &(**region) as *const RefCell<TextureRegion>;
+                if let (true, Some((rect_x, rect_y, rect_width, rect_height))) = (is_shared, texture_internal.rect) {
+                    let (rect_x, rect_y) = (cmp::min(rect_x, width), cmp::min(rect_y, height));
+                    texture_internal.rect = Some((rect_x, rect_y, cmp::min(rect_width, width - rect_x),
+                                                  cmp::min(rect_height, height - rect_y)));
+                }
+            }
         }
+        region.borrow().write_pixels(&pixels);
+        Ok(())
+    }
+
+    /// Sets the packer used for texture atlases that are created from now on.
+    pub fn set_packing_strategy(&mut self, strategy: PackingStrategy) {
+        self.packing_strategy = strategy;
+    }
+
+    /// Sets how many glyph cache pages may exist before the least recently used one is cleared
+    /// for new glyphs.
+    pub fn set_glyph_cache_pages(&mut self, pages: usize) {
+        self.glyph_cache.borrow_mut().set_max_pages(pages);
+    }
+
+    /// Sets the number of mip levels below the full resolution of the current and all following
+    /// texture atlases, 0 disables mipmapping. Textures are packed with a gutter of `2^levels`
+    /// pixels so the levels don't bleed into each other, e.g. 3 levels keep textures sharp down
+    /// to an eighth of their size. Textures that are already packed keep their gutter and limit
+    /// the chain of their atlas until `compact_atlases` repacks them.
+    pub fn set_mip_levels(&mut self, levels: u32) {
+        self.mip_levels = levels;
+        let mut atlas = self.current_atlas.borrow_mut();
+        atlas.set_gutter(get_atlas_gutter(levels));
+        atlas.set_mipmaps(levels > 0);
+    }
+
+    pub fn get_mip_levels(&self) -> u32 {
+        self.mip_levels
     }
 
     pub fn intern_string(&self, string: &str) -> StringId {
         StringId::new(string, Rc::downgrade(&self.string_id_table))
     }
 
-    pub fn get_texture(&self, string: &str) -> Option<Texture> {
-        unimplemented!();
+    pub fn get_texture(&self, string: &str) -> Option<TextureHandle> {
+        let texture_name = self.intern_string(string);
+        self.textures.get(&texture_name.id).cloned()
     }
 
-    pub fn create_texture(&mut self, string: &str) -> Result<Texture> {
-        let texture_name = self.intern_string(string);
+    /// Returns the atlas and UVs behind `handle`. Handles of unloaded textures resolve to a
+    /// magenta and black checkerboard.
+    pub fn resolve_texture(&self, handle: TextureHandle) -> Texture {
+        match self.texture_slots.get(handle.index as usize, handle.generation) {
+            Some(texture_internal) => Texture {
+                name: texture_internal.name.clone(),
+                region: texture_internal.region.clone(),
+                rect: texture_internal.rect,
+                transform: handle.transform,
+            },
+            None => self.missing_texture.clone(),
+        }
+    }
+
+    /// Creates a texture that shows the rectangle at `x`, `y` with `width` x `height` logical units
+    /// of `texture`, measured as it is shown with the flips and rotation of the handle. For a
+    /// texture loaded from a HiDPI variant these are multiplied by its scale, like the frame size
+    /// of `create_sprite_sheet_grid`, so the rectangle is the same for every variant. The new
+    /// handle is shown with the same flips and rotation. Sub textures share the atlas space of
+    /// their texture, it is only freed once all of them are unloaded. Returns `None` if `texture`
+    /// was unloaded, hasn't finished loading or the rectangle isn't inside of it.
+    pub fn create_sub_texture(&mut self, texture: TextureHandle, x: u32, y: u32, width: u32, height: u32) -> Option<TextureHandle> {
+        let (name, region, rect) = match self.texture_slots.get(texture.index as usize, texture.generation) {
+            Some(texture_internal) => (texture_internal.name.get_name(), texture_internal.region.clone(), texture_internal.rect),
+            None => return None,
+        };
+        if region.borrow().state != LoadState::Loaded {
+            return None;
+        }
+        let transform = texture.transform;
+        let (shown_width, shown_height) = self.resolve_texture(texture).get_size();
+        let scale = region.borrow().scale;
+        let to_pixels = |value: u32| (value as f32 * scale).round() as u32;
+        let (x, y, width, height) = (to_pixels(x), to_pixels(y), to_pixels(width), to_pixels(height));
+        let inside = x.checked_add(width).map_or(false, |right| right <= shown_width) &&
+                     y.checked_add(height).map_or(false, |bottom| bottom <= shown_height);
+        if width == 0 || height == 0 || !inside {
+            return None;
+        }
+
+        // the rectangle in the upright image of the texture
+        let (base_x, base_y, base_width, base_height) = rect.unwrap_or({
+            let size = region.borrow().size;
+            (0, 0, size.0, size.1)
+        });
+        let a = transform.to_source(x as f32 / shown_width as f32, y as f32 / shown_height as f32);
+        let b = transform.to_source((x + width) as f32 / shown_width as f32, (y + height) as f32 / shown_height as f32);
+        let (sub_width, sub_height) = if transform.is_turned_sideways() { (height, width) } else { (width, height) };
+        let sub_rect = (base_x + (a.0.min(b.0) * base_width as f32).round() as u32,
+                        base_y + (a.1.min(b.1) * base_height as f32).round() as u32,
+                        sub_width, sub_height);
+
+        let sub_name = format!("{}#{},{},{},{}", name, sub_rect.0, sub_rect.1, sub_rect.2, sub_rect.3);
+        if let Some(sub_texture) = self.get_texture(&sub_name) {
+            return Some(sub_texture.with_transform(transform));
+        }
+        let texture_name = self.intern_string(&sub_name);
+        let id = texture_name.id;
+        let (index, generation) = self.texture_slots.insert(TextureInternal {
+            name: texture_name,
+            region: region,
+            rect: Some(sub_rect),
+            source: None,
+        });
+        let handle = TextureHandle { index: index as u32, generation: generation, transform: TextureTransform::default() };
+        self.textures.insert(id, handle);
+        Some(handle.with_transform(transform))
+    }
+
+    /// Unloads a texture, `handle` and all copies of it resolve to the missing texture from now
+    /// on. Its atlas space is reused by the next textures, so resolved `Texture`s of it must not be
+    /// drawn anymore.
+    pub fn unload_texture(&mut self, handle: TextureHandle) {
+        if let Some(texture_internal) = self.texture_slots.remove(handle.index as usize, handle.generation) {
+            self.release_region(&texture_internal.region);
+            // a failed async load isn't registered under its name anymore
+            let is_registered = match self.textures.get(&texture_internal.name.id) {
+                Some(registered) => registered.is_same_texture(&handle),
+                None => false,
+            };
+            if is_registered {
+                self.textures.remove(&texture_internal.name.id);
+            }
+        }
+    }
 
-        if let Some(texture_internal) = self.textures.get(&texture_name.id) {
-            if let Some(atlas) = texture_internal.atlas.upgrade() {
-                return Ok(Texture {
-                    name: texture_name,
+    /// Frees the atlas space of `region` unless another texture, e.g. a sub texture, still
+    /// shows it.
+    fn release_region(&self, region: &Rc<RefCell<TextureRegion>>) {
+        let is_shared = self.texture_slots.iter().any(|(_, _, texture_internal)| {
+            &(*texture_internal.region) as *const RefCell<TextureRegion> == &(**region) as *const RefCell<TextureRegion>
+        });
+        if !is_shared {
+            free_region(&region.borrow());
+        }
+    }
+
+    pub fn get_font(&self, string: &str, size: u32) -> Option<FontHandle> {
+        self.get_font_with_mode(string, size, GlyphMode::Coverage)
+    }
+
+    pub fn get_font_with_mode(&self, string: &str, size: u32, mode: GlyphMode) -> Option<FontHandle> {
+        let font_name = self.intern_string(&font_key(string, size, mode));
+        self.fonts.get(&font_name.id).cloned()
+    }
+
+    /// Returns the font behind `handle`, or `None` if it was unloaded.
+    pub fn resolve_font(&self, handle: FontHandle) -> Option<Font> {
+        self.font_slots.get(handle.index as usize, handle.generation).map(|font_internal| {
+            Font {
+                name: font_internal.name.clone(),
+                path: font_internal.path.clone(),
+                size: font_internal.size,
+                mode: font_internal.mode,
+                metrics: font_internal.metrics,
+                face: font_internal.face,
+                fallbacks: font_internal.fallbacks.clone(),
+                glyph_cache: self.glyph_cache.clone(),
+            }
+        })
+    }
+
+    /// Appends the face of `fallback` to the fallback list of `font`. Its glyphs are rasterized at
+    /// the size of `font`.
+    pub fn add_font_fallback(&mut self, font: FontHandle, fallback: FontHandle) {
+        let face = match self.font_slots.get(fallback.index as usize, fallback.generation) {
+            Some(font_internal) => font_internal.face,
+            None => return,
+        };
+        if let Some(font_internal) = self.font_slots.get_mut(font.index as usize, font.generation) {
+            if face != font_internal.face && !font_internal.fallbacks.contains(&face) {
+                font_internal.fallbacks.push(face);
+            }
+        }
+    }
+
+    /// Unloads a font, `handle` and all copies of it stop resolving. Its glyphs are removed from
+    /// the glyph cache, the face stays loaded for the other sizes of the font.
+    pub fn unload_font(&mut self, handle: FontHandle) {
+        if let Some(font_internal) = self.font_slots.remove(handle.index as usize, handle.generation) {
+            self.fonts.remove(&font_internal.name.id);
+            self.glyph_cache.borrow_mut().remove_glyphs(font_internal.face, font_internal.size, font_internal.mode);
+        }
+    }
+
+    /// Limits the memory of the atlases of `category` to `bytes`, `None` removes the limit.
+    /// Creating a texture that needs a new atlas beyond the budget fails with
+    /// `ResourceManagerError::OverBudget`, mip chains count towards the texture budget. The font
+    /// budget sets the number of glyph cache pages, see `set_glyph_cache_pages`. A page of a glyph
+    /// too large for a regular page counts as one page although it is larger, and pages with
+    /// bitmap font glyphs are never evicted, so these can exceed the font budget.
+    pub fn set_memory_budget(&mut self, category: ResourceCategory, bytes: Option<usize>) {
+        match category {
+            ResourceCategory::Textures => self.texture_budget = bytes,
+            ResourceCategory::Fonts => {
+                let mut glyph_cache = self.glyph_cache.borrow_mut();
+                let pages = match bytes {
+                    Some(bytes) => cmp::max(1, bytes / glyph_cache.get_page_bytes()),
+                    None => usize::MAX,
+                };
+                glyph_cache.set_max_pages(pages);
+            }
+        }
+    }
+
+    /// Bytes of main memory used by the atlases of `category`.
+    pub fn get_memory_usage(&self, category: ResourceCategory) -> usize {
+        self.get_atlases(category).iter().map(|atlas| atlas.borrow().get_cpu_bytes()).sum()
+    }
+
+    /// Lists the memory use of every atlas.
+    pub fn get_atlas_stats(&self) -> Vec<AtlasStats> {
+        let mut result = Vec::new();
+        for &category in &[ResourceCategory::Textures, ResourceCategory::Fonts] {
+            for atlas in self.get_atlases(category) {
+                let (dimensions, cpu_bytes, gpu_bytes, packer) = {
+                    let atlas = atlas.borrow();
+                    (atlas.get_dimensions(), atlas.get_cpu_bytes(), atlas.get_gpu_bytes(), atlas.get_stats())
+                };
+                result.push(AtlasStats {
+                    category: category,
                     atlas: atlas,
-                    uv_min: texture_internal.uv_min,
-                    uv_max: texture_internal.uv_max,
-                    pixel_dimension: texture_internal.pixel_dimension,
-                })
+                    dimensions: dimensions,
+                    cpu_bytes: cpu_bytes,
+                    gpu_bytes: gpu_bytes,
+                    packer: packer,
+                });
+            }
+        }
+        result
+    }
+
+    /// Atlases that are still referenced by `category`, every atlas is listed once.
+    fn get_atlases(&self, category: ResourceCategory) -> Vec<Rc<RefCell<TextureAtlas>>> {
+        if category == ResourceCategory::Fonts {
+            return self.glyph_cache.borrow().get_page_atlases();
+        }
+        let mut result: Vec<Rc<RefCell<TextureAtlas>>> = vec![self.current_atlas.clone()];
+        let regions = self.texture_slots.iter().map(|(_, _, texture_internal)| texture_internal.region.clone())
+            .chain(Some(self.missing_texture.region.clone()))
+            .chain(self.placeholder.clone());
+        for region in regions {
+            let atlas = region.borrow().atlas.clone();
+            let is_listed = result.iter().any(|listed| {
+                &(*atlas) as *const RefCell<TextureAtlas> == &(**listed) as *const RefCell<TextureAtlas>
+            });
+            if !is_listed {
+                result.push(atlas);
+            }
+        }
+        result
+    }
+
+    /// Lists every texture that hasn't been unloaded.
+    pub fn get_texture_infos(&self) -> Vec<TextureInfo> {
+        let mut result = Vec::new();
+        for (_, _, texture_internal) in self.texture_slots.iter() {
+            // sub textures only cover their rect of the region
+            let texture = Texture {
+                name: texture_internal.name.clone(),
+                region: texture_internal.region.clone(),
+                rect: texture_internal.rect,
+                transform: TextureTransform::default(),
+            };
+            result.push(TextureInfo {
+                name: texture_internal.name.clone(),
+                atlas: texture.get_atlas(),
+                uv_min: texture.get_uv_min(),
+                uv_max: texture.get_uv_max(),
+                size: texture.get_size(),
+            });
+        }
+        result
+    }
+
+    /// Lists every font that hasn't been unloaded.
+    pub fn get_font_infos(&self) -> Vec<FontInfo> {
+        let mut result = Vec::new();
+        let glyph_cache = self.glyph_cache.borrow();
+        for (_, _, font_internal) in self.font_slots.iter() {
+            result.push(FontInfo {
+                name: font_internal.name.clone(),
+                path: font_internal.path.clone(),
+                size: font_internal.size,
+                atlases: glyph_cache.get_atlases(font_internal.face, font_internal.size),
+                glyph_count: glyph_cache.get_glyph_count(font_internal.face, font_internal.size),
+            });
+        }
+        result
+    }
+
+    pub fn create_texture(&mut self, string: &str) -> Result<TextureHandle> {
+        if let Some(texture) = self.get_texture(string) {
+            return Ok(texture);
+        }
+        let (path, scale) = self.find_variant(string);
+        let data = try!(self.read_asset(&path));
+        let img = try!(image::load_from_memory(&data));
+        let source = self.get_asset_modified(&path).map(|modified| (path.clone(), modified));
+        self.insert_texture(string, img, scale, source)
+    }
+
+    /// Like `create_texture`, but decodes the image from `data` (e.g. from `include_bytes!`)
+    /// instead of a file. `string` is the name the texture is registered under.
+    pub fn create_texture_from_memory(&mut self, string: &str, data: &[u8]) -> Result<TextureHandle> {
+        if let Some(texture) = self.get_texture(string) {
+            return Ok(texture);
+        }
+        let img = try!(image::load_from_memory(data));
+        self.create_texture_from_image(string, img)
+    }
+
+    /// Packs an already decoded image, e.g. one that was generated at runtime, into the atlas
+    /// and registers it under `string`.
+    pub fn create_texture_from_image(&mut self, string: &str, img: image::DynamicImage) -> Result<TextureHandle> {
+        if let Some(texture) = self.get_texture(string) {
+            return Ok(texture);
+        }
+        self.insert_texture(string, img, 1.0, None)
+    }
+
+    /// Like `create_texture`, but decodes the image on a worker thread. Until
+    /// `finish_async_loads` swaps in the decoded image the texture shows a transparent
+    /// placeholder and `Texture::get_load_state` of the resolved handle returns
+    /// `LoadState::Loading`.
+    pub fn create_texture_async(&mut self, string: &str) -> Result<TextureHandle> {
+        if let Some(texture) = self.get_texture(string) {
+            return Ok(texture);
+        }
+        let (path, scale) = self.find_variant(string);
+        let data = try!(self.read_asset(&path));
+        let placeholder = try!(self.get_placeholder());
+        let texture_name = self.intern_string(string);
+
+        if self.async_loader.is_none() {
+            self.async_loader = Some(AsyncLoader::new(ASYNC_WORKER_COUNT));
+        }
+        if let Err(err) = self.async_loader.as_ref().unwrap().decode(texture_name.id, data) {
+            // the next async load starts new workers
+            self.async_loader = None;
+            return Err(err);
+        }
+
+        let mut texture_region = placeholder.borrow().clone();
+        texture_region.state = LoadState::Loading;
+        texture_region.scale = scale;
+        let source = self.get_asset_modified(&path).map(|modified| (path.clone(), modified));
+        let handle = self.add_texture(texture_name.clone(), Rc::new(RefCell::new(texture_region)), source);
+        self.pending_textures.insert(texture_name.id, handle);
+        Ok(handle)
+    }
+
+    /// Packs the images that were decoded for `create_texture_async` into the atlas and updates
+    /// their textures. Has to be called regularly on the main thread, e.g. once per frame.
+    /// Returns the number of textures that finished loading (or failed to) and the names of the
+    /// ones that failed together with the error. Textures that failed are no longer registered
+    /// under their name, so creating them again retries the load.
+    pub fn finish_async_loads(&mut self) -> (usize, Vec<(String, ResourceManagerError)>) {
+        let mut results = Vec::new();
+        if let Some(ref async_loader) = self.async_loader {
+            while let Some(result) = async_loader.try_recv() {
+                results.push(result);
+            }
+        }
+
+        let count = results.len();
+        let mut failed = Vec::new();
+        for (id, result) in results {
+            let handle = match self.pending_textures.remove(&id) {
+                Some(handle) => handle,
+                None => continue,
+            };
+            // the texture was unloaded while decoding
+            let (name, region) = match self.texture_slots.get(handle.index as usize, handle.generation) {
+                Some(texture_internal) => (texture_internal.name.get_name(), texture_internal.region.clone()),
+                None => continue,
+            };
+
+            let img = match result {
+                Ok(img) => img,
+                Err(err) => {
+                    region.borrow_mut().state = LoadState::Failed;
+                    self.textures.remove(&id);
+                    failed.push((name, ResourceManagerError::from(err)));
+                    continue;
+                }
+            };
+            let (width, height) = img.dimensions();
+            match self.allocate_region(width, height) {
+                Ok((atlas, x, y, rotated)) => {
+                    let mut region = region.borrow_mut();
+                    region.rotated = rotated;
+                    region.move_to(atlas, x, y, width, height);
+                    region.state = LoadState::Loaded;
+                    region.write_pixels(&img.to_rgba().into_raw());
+                }
+                Err(err) => {
+                    region.borrow_mut().state = LoadState::Failed;
+                    self.textures.remove(&id);
+                    failed.push((name, err));
+                }
             }
         }
-        self.textures.remove(&texture_name.id);
+        (count, failed)
+    }
 
-        let img = try!(image::open(&Path::new(string)));
+    fn get_placeholder(&mut self) -> Result<Rc<RefCell<TextureRegion>>> {
+        if let Some(ref placeholder) = self.placeholder {
+            return Ok(placeholder.clone());
+        }
+        let (atlas, x, y, _) = try!(self.allocate_region(1, 1));
+        atlas.borrow_mut().set_region(x, y, 1, 1, &vec![0, 0, 0, 0], 4);
+        let placeholder = Rc::new(RefCell::new(TextureRegion::new(atlas, x, y, 1, 1)));
+        self.placeholder = Some(placeholder.clone());
+        Ok(placeholder)
+    }
+
+    /// Allocates a region in the current atlas or in space that was freed in an older atlas. Only
+    /// if neither has room a new atlas is started, as long as the texture budget allows it. Images
+    /// that only fit when turned sideways are rotated, which is returned as the last value.
+    /// Atlases that are merely fragmented aren't compacted automatically, since that moves every
+    /// texture, see `compact_atlases`.
+    fn allocate_region(&mut self, width: u32, height: u32) -> Result<(Rc<RefCell<TextureAtlas>>, usize, usize, bool)> {
+        self.older_atlases.retain(|atlas| atlas.upgrade().is_some());
+        let mut atlases = vec![self.current_atlas.clone()];
+        atlases.extend(self.older_atlases.iter().filter_map(|atlas| atlas.upgrade()));
+        for atlas in atlases {
+            if let Some((x, y, rotated)) = place_region(&atlas, width, height) {
+                return Ok((atlas, x, y, rotated));
+            }
+        }
+
+        let atlas = create_atlas(self.packing_strategy, self.mip_levels);
+        // the new atlas is charged with its mip chain, it is built on the first upload
+        try!(self.check_texture_budget(atlas.borrow().get_reserved_bytes()));
+        self.older_atlases.push(Rc::downgrade(&self.current_atlas));
+        self.current_atlas = atlas.clone();
+        match place_region(&atlas, width, height) {
+            Some((x, y, rotated)) => Ok((atlas, x, y, rotated)),
+            None => Err(ResourceManagerError::TooLarge),
+        }
+    }
+
+    /// Fails with `ResourceManagerError::OverBudget` if `bytes` of new atlases don't fit into the
+    /// texture budget next to the existing ones.
+    fn check_texture_budget(&self, bytes: usize) -> Result<()> {
+        if let Some(budget) = self.texture_budget {
+            let reserved = self.get_atlases(ResourceCategory::Textures).iter()
+                .map(|atlas| atlas.borrow().get_reserved_bytes())
+                .sum::<usize>();
+            if reserved + bytes > budget {
+                return Err(ResourceManagerError::OverBudget);
+            }
+        }
+        Ok(())
+    }
+
+    fn insert_texture(&mut self, string: &str, img: image::DynamicImage, scale: f32, source: Option<(String, SystemTime)>) -> Result<TextureHandle> {
+        let texture_name = self.intern_string(string);
         let (width, height) = img.dimensions();
-        let mut uv_min: (f32, f32) = (0.0, 0.0);
-        let mut uv_max: (f32, f32) = (0.0, 0.0);
-        let pixel_dimension = 1.0f32 / ATLAS_DIMENSION as f32;
-
-        let attempt_set_region = |this: &mut ResourceManager, uv_min: &mut (f32, f32), uv_max: &mut (f32, f32)| -> bool {
-            let mut atlas = this.current_atlas.borrow_mut();
-            let region = match atlas.get_region(width as usize, height as usize) {
-                Some(region) => region,
-                None => return false,
+        let (atlas, x, y, rotated) = try!(self.allocate_region(width, height));
+        let mut texture_region = TextureRegion::new(atlas.clone(), x, y, width, height);
+        if rotated {
+            texture_region.rotated = true;
+            texture_region.move_to(atlas, x, y, width, height);
+        }
+        texture_region.scale = scale;
+        texture_region.write_pixels(&img.to_rgba().into_raw());
+        Ok(self.add_texture(texture_name, Rc::new(RefCell::new(texture_region)), source))
+    }
+
+    /// Registers `region` under `name`. A texture that is already registered under the name keeps
+    /// its handle and shows the new region from now on.
+    fn add_texture(&mut self, name: StringId, region: Rc<RefCell<TextureRegion>>, source: Option<(String, SystemTime)>) -> TextureHandle {
+        if let Some(&handle) = self.textures.get(&name.id) {
+            let old_region = {
+                let texture_internal = self.texture_slots.get_mut(handle.index as usize, handle.generation).unwrap();
+                texture_internal.rect = None;
+                texture_internal.source = source;
+                mem::replace(&mut texture_internal.region, region)
             };
-            atlas.set_region(region.0, region.1, region.2, region.3, &img.raw_pixels(), width as usize * 4);
-            *uv_min = (region.0 as f32 / ATLAS_DIMENSION as f32, region.1 as f32 / ATLAS_DIMENSION as f32);
-            *uv_max = (uv_min.0 as f32 + width as f32 * pixel_dimension, uv_min.1 as f32 + height as f32 * pixel_dimension);
-            return true;
-            /*if let Some(region) = atlas.get_region(width as usize, height as usize) {
-                atlas.set_region(region.0, region.1, region.2, region.3, &img.raw_pixels(), width as usize * 4);
-                *uv_min = (region.0 as f32 / ATLAS_DIMENSION as f32, region.1 as f32 / ATLAS_DIMENSION as f32);
-                *uv_max = (uv_min.0 as f32 + width as f32 * pixel_dimension, uv_min.1 as f32 + height as f32 * pixel_dimension);
-                return true;
-            }
-            return false;*/
+            self.release_region(&old_region);
+            return handle;
+        }
+        let id = name.id;
+        let (index, generation) = self.texture_slots.insert(TextureInternal {
+            name: name,
+            region: region,
+            rect: None,
+            source: source,
+        });
+        let handle = TextureHandle { index: index as u32, generation: generation, transform: TextureTransform::default() };
+        self.textures.insert(id, handle);
+        handle
+    }
+
+    /// Loads an atlas that was baked with `AtlasBaker`. The pages are decoded once and every
+    /// region becomes a texture that `get_texture` and `create_texture` resolve by its name.
+    /// HiDPI variants of the atlas have to name their regions like the atlas for a scale of 1.
+    /// The pages count towards the texture budget like any other atlas.
+    pub fn load_baked_atlas(&mut self, string: &str) -> Result<()> {
+        let (path, scale) = self.find_variant(string);
+        let manifest = try!(self.read_asset(&path));
+        let baked_atlas = try!(BakedAtlas::from_reader(&manifest[..], &path));
+        let directory = match path.rfind('/') {
+            Some(i) => &path[..i + 1],
+            None => "",
         };
+        let bytes = baked_atlas.pages.iter().map(|&(width, height, _)| width * height * 4).sum::<usize>();
+        try!(self.check_texture_budget(bytes));
 
-        let mut success;
-        success = attempt_set_region(self, &mut uv_min, &mut uv_max);
-        if !success {
-            self.current_atlas = Rc::new(RefCell::new(TextureAtlas::new(ATLAS_DIMENSION, ATLAS_DIMENSION, 4)));
-            success = attempt_set_region(self, &mut uv_min, &mut uv_max);
+        let mut pages = Vec::new();
+        for &(width, height, ref page) in &baked_atlas.pages {
+            let page_data = try!(self.read_asset(&format!("{}{}", directory, page)));
+            let img = try!(image::load_from_memory(&page_data));
+            let (img_width, img_height) = img.dimensions();
+            if img_width as usize != width || img_height as usize != height {
+                return Err(ResourceManagerError::InvalidManifest(format!("{}: page {} has the wrong size", path, page)));
+            }
+            let atlas = TextureAtlas::new_with_data(width, height, 4, img.to_rgba().into_raw());
+            pages.push(Rc::new(RefCell::new(atlas)));
         }
-        if !success {
-            return Err(ResourceManagerError::TooLarge);
+
+        for region in &baked_atlas.regions {
+            let texture_name = self.intern_string(&region.name);
+            let mut texture_region = TextureRegion::new(pages[region.page].clone(), region.position.0, region.position.1,
+                                                        region.size.0, region.size.1);
+            texture_region.insets = region.insets;
+            texture_region.scale = scale;
+            self.add_texture(texture_name, Rc::new(RefCell::new(texture_region)), None);
         }
+        Ok(())
+    }
 
-        self.textures.insert(texture_name.id, TextureInternal {
-            atlas: Rc::downgrade(&self.current_atlas),
-            uv_min: (uv_min.0, uv_min.1),
-            uv_max: (uv_max.0, uv_max.1),
-            pixel_dimension: pixel_dimension,
-        });
+    /// Loads the JSON export of a sprite sheet from Aseprite or TexturePacker (hash or array
+    /// format) together with its image. Every frame is packed into the atlas as a texture of its
+    /// own. Frames without a duration are shown for 0.1 seconds.
+    pub fn create_sprite_sheet(&mut self, string: &str) -> Result<SpriteSheet> {
+        let (path, scale) = self.find_variant(string);
+        let data = try!(self.read_asset(&path));
+        let layout = try!(SheetLayout::parse_json(&data, &path));
+        let directory = match path.rfind('/') {
+            Some(i) => &path[..i + 1],
+            None => "",
+        };
+        let image_data = match layout.image {
+            Some(ref image) => try!(self.read_asset(&format!("{}{}", directory, image))),
+            None => return Err(ResourceManagerError::InvalidManifest(format!("{}: no image", path))),
+        };
+        let img = try!(image::load_from_memory(&image_data));
+        self.insert_sprite_sheet(string, img, scale, layout)
+    }
 
-        Ok(Texture {
-            name: texture_name,
-            atlas: self.current_atlas.clone(),
-            uv_min: (uv_min.0, uv_min.1),
-            uv_max: (uv_max.0, uv_max.1),
-            pixel_dimension: pixel_dimension,
-        })
+    /// Cuts the image `string` into frames of `frame_width` x `frame_height` logical units, row by
+    /// row. For a HiDPI variant of the image these are multiplied by its scale, like the rectangle
+    /// of `create_sub_texture`. Every frame is shown for `duration` seconds.
+    pub fn create_sprite_sheet_grid(&mut self, string: &str, frame_width: u32, frame_height: u32, duration: f32) -> Result<SpriteSheet> {
+        let (path, scale) = self.find_variant(string);
+        let data = try!(self.read_asset(&path));
+        let img = try!(image::load_from_memory(&data));
+        let (width, height) = img.dimensions();
+        let (frame_width, frame_height) = ((frame_width as f32 * scale).round() as u32, (frame_height as f32 * scale).round() as u32);
+        let layout = SheetLayout::grid(width, height, frame_width, frame_height, duration);
+        self.insert_sprite_sheet(string, img, scale, layout)
     }
 
-    pub fn create_font(&mut self, string: &str, size: u32) -> Result<Font> {
-        let font_name_string = format!("{}{}", string, size);
-        let font_name = self.intern_string(&font_name_string);
-
-        if let Some(font_internal) = self.fonts.get(&font_name.id) {
-            if let Some(atlas) = font_internal.atlas.upgrade() {
-                if let Some(glyphs) = font_internal.glyphs.upgrade() {
-                    if let Some(kernings) = font_internal.kernings.upgrade() {
-                        return Ok(Font {
-                            name: font_name,
-                            path: string.to_string(),
-                            atlas: atlas,
-                            glyphs: glyphs,
-                            kernings: kernings,
-                            size: size as f32,
-                        })
+    fn insert_sprite_sheet(&mut self, string: &str, mut img: image::DynamicImage, scale: f32, layout: SheetLayout) -> Result<SpriteSheet> {
+        let (width, height) = img.dimensions();
+        let mut frames = Vec::new();
+        for frame in &layout.frames {
+            let frame_name = format!("{}#{}", string, frame.name);
+            let texture = match self.get_texture(&frame_name) {
+                Some(texture) => texture,
+                None => {
+                    let ((x, y), (frame_width, frame_height)) = (frame.position, frame.size);
+                    let inside = x.checked_add(frame_width).map_or(false, |right| right <= width) &&
+                                 y.checked_add(frame_height).map_or(false, |bottom| bottom <= height);
+                    if !inside {
+                        return Err(ResourceManagerError::InvalidManifest(format!("{}: frame {} is outside of the image", string, frame.name)));
                     }
+                    // frames aren't hot-reloaded, the file behind them is the whole sheet
+                    let frame_img = img.crop(x, y, frame_width, frame_height);
+                    try!(self.insert_texture(&frame_name, frame_img, scale, None))
                 }
+            };
+            frames.push(SpriteFrame {
+                texture: texture,
+                offset: (frame.source_offset.0 as f32 / scale, frame.source_offset.1 as f32 / scale),
+                source_size: (frame.source_size.0 as f32 / scale, frame.source_size.1 as f32 / scale),
+                duration: frame.duration,
+            });
+        }
+        Ok(SpriteSheet {
+            frames: frames,
+            tags: layout.tags,
+        })
+    }
+
+    /// Repacks every loaded texture into as few atlases as possible, e.g. after many textures
+    /// were unloaded. Handles stay valid, but draw batches that were built before have to
+    /// be rebuilt and the new atlases have to be uploaded. If a texture can't be placed or the new
+    /// atlases exceed the texture budget nothing is moved.
+    pub fn compact_atlases(&mut self) -> Result<()> {
+        let mut live: Vec<(Rc<RefCell<TextureRegion>>, Vec<u8>)> = Vec::new();
+        let mut waiting = Vec::new();
+        let missing_region = self.missing_texture.region.clone();
+        for region in self.texture_slots.iter().map(|(_, _, texture_internal)| texture_internal.region.clone())
+                                               .chain(Some(missing_region)) {
+            // textures that are still loading or failed to load show the placeholder, they get
+            // the new one instead of space of their own
+            if region.borrow().state != LoadState::Loaded {
+                waiting.push(region);
+                continue;
+            }
+            // sub textures share the region of their texture
+            let is_listed = live.iter().any(|&(ref listed, _)| {
+                &(**listed) as *const RefCell<TextureRegion> == &(*region) as *const RefCell<TextureRegion>
+            });
+            if is_listed {
+                continue;
             }
+            let data = {
+                let region = region.borrow();
+                let (width, height) = region.get_footprint();
+                let atlas = region.atlas.borrow();
+                atlas.get_region_data(region.position.0, region.position.1, width as usize, height as usize)
+            };
+            live.push((region, data));
         }
-        self.fonts.remove(&font_name.id);
 
-        let font_atlas = Rc::new(RefCell::new(TextureAtlas::new(FONT_ATLAS_DIMENSION, FONT_ATLAS_DIMENSION, 1)));
-        let glyphs = Rc::new(RefCell::new(HashMap::new()));
-        let kernings = Rc::new(RefCell::new(HashMap::new()));
+        let placeholder = if waiting.is_empty() {
+            None
+        } else {
+            let placeholder = Rc::new(RefCell::new(TextureRegion::new(self.current_atlas.clone(), 0, 0, 1, 1)));
+            live.push((placeholder.clone(), vec![0, 0, 0, 0]));
+            Some(placeholder)
+        };
+
+        // placing the tallest images first keeps the skyline flat, rotated images stay rotated
+        live.sort_by(|a, b| b.0.borrow().get_footprint().1.cmp(&a.0.borrow().get_footprint().1));
 
-        self.fonts.insert(font_name.id, FontInternal {
-            atlas: Rc::downgrade(&font_atlas),
-            glyphs: Rc::downgrade(&glyphs),
-            kernings: Rc::downgrade(&kernings),
-        });
+        // everything is placed before the first region is moved, so a failure leaves the
+        // textures where they were
+        let mut atlases = vec![create_atlas(self.packing_strategy, self.mip_levels)];
+        let mut placements = Vec::new();
+        for (region, data) in live {
+            let (width, height) = region.borrow().get_footprint();
+            let mut placed = None;
+            for atlas in &atlases {
+                if let Some(atlas_region) = atlas.borrow_mut().get_region(width as usize, height as usize) {
+                    placed = Some((atlas.clone(), atlas_region));
+                    break;
+                }
+            }
+            let (atlas, atlas_region) = match placed {
+                Some(placed) => placed,
+                None => {
+                    let atlas = create_atlas(self.packing_strategy, self.mip_levels);
+                    let atlas_region = match atlas.borrow_mut().get_region(width as usize, height as usize) {
+                        Some(atlas_region) => atlas_region,
+                        None => return Err(ResourceManagerError::TooLarge),
+                    };
+                    atlases.push(atlas.clone());
+                    (atlas, atlas_region)
+                }
+            };
+            atlas.borrow_mut().set_region(atlas_region.0, atlas_region.1, atlas_region.2, atlas_region.3,
+                                          &data, width as usize * 4);
+            placements.push((region, atlas, atlas_region));
+        }
+        // the old atlases are released once everything moved, so the new ones alone have to
+        // fit into the budget
+        if let Some(budget) = self.texture_budget {
+            if atlases.iter().map(|atlas| atlas.borrow().get_reserved_bytes()).sum::<usize>() > budget {
+                return Err(ResourceManagerError::OverBudget);
+            }
+        }
+        for (region, atlas, atlas_region) in placements {
+            let size = region.borrow().size;
+            region.borrow_mut().move_to(atlas, atlas_region.0, atlas_region.1, size.0, size.1);
+        }
+
+        // the other atlases are kept alive by the textures that were moved into them
+        self.current_atlas = atlases.pop().unwrap();
+        self.older_atlases = atlases.iter().map(|atlas| Rc::downgrade(atlas)).collect();
+        if let Some(ref placeholder) = placeholder {
+            for region in &waiting {
+                let mut moved = placeholder.borrow().clone();
+                moved.state = region.borrow().state;
+                moved.scale = region.borrow().scale;
+                *region.borrow_mut() = moved;
+            }
+        }
+        self.placeholder = placeholder;
+        Ok(())
+    }
+
+    pub fn create_font(&mut self, string: &str, size: u32) -> Result<FontHandle> {
+        self.create_font_with_mode(string, size, GlyphMode::Coverage)
+    }
+
+    /// Like `create_font`, but with the glyphs stored as `mode`. With
+    /// `GlyphMode::DistanceField` a single size can be drawn at any scale, the size should then
+    /// be large enough for the finest details of the font, e.g. 32.
+    pub fn create_font_with_mode(&mut self, string: &str, size: u32, mode: GlyphMode) -> Result<FontHandle> {
+        if let Some(font) = self.get_font_with_mode(string, size, mode) {
+            return Ok(font);
+        }
+        // the file is only read and parsed for the first size of a face
+        let face = self.glyph_cache.borrow().get_face(string);
+        let face = match face {
+            Some(face) => face,
+            None => {
+                let data = try!(self.read_asset(string));
+                try!(self.glyph_cache.borrow_mut().add_face(string, data))
+            }
+        };
+        let modified = self.get_asset_modified(string);
+        let metrics = self.glyph_cache.borrow().get_metrics(face, size as f32);
+        Ok(self.register_font(string, face, size as f32, mode, metrics, modified))
+    }
+
+    /// Creates the font `string` with `fallbacks` as its fallbacks in the given order, e.g. a
+    /// latin font followed by a CJK and a symbol font.
+    pub fn create_font_with_fallbacks(&mut self, string: &str, fallbacks: &[&str], size: u32) -> Result<FontHandle> {
+        let font = try!(self.create_font(string, size));
+        for string in fallbacks {
+            let fallback = try!(self.create_font(string, size));
+            self.add_font_fallback(font, fallback);
+        }
+        Ok(font)
+    }
+
+    /// Like `create_font`, but reads the font file from `data` instead of the file system.
+    /// `string` is the name the font is registered under.
+    pub fn create_font_from_memory(&mut self, string: &str, data: &[u8], size: u32) -> Result<FontHandle> {
+        if let Some(font) = self.get_font(string, size) {
+            return Ok(font);
+        }
+        let face = self.glyph_cache.borrow().get_face(string);
+        let face = match face {
+            Some(face) => face,
+            None => try!(self.glyph_cache.borrow_mut().add_face(string, data.to_vec())),
+        };
+        let metrics = self.glyph_cache.borrow().get_metrics(face, size as f32);
+        Ok(self.register_font(string, face, size as f32, GlyphMode::Coverage, metrics, None))
+    }
+
+    /// Loads an AngelCode BMFont `.fnt` file in the text or binary format together with its page
+    /// images. The glyphs are copied into the glyph cache, so the font is used like any other font.
+    /// Page images with an alpha channel are used as coverage, others by their brightness.
+    pub fn create_bitmap_font(&mut self, string: &str) -> Result<FontHandle> {
+        if let Some(font) = self.get_font_with_mode(string, 0, GlyphMode::Bitmap) {
+            return Ok(font);
+        }
+        let data = try!(self.read_asset(string));
+        let bitmap_font = try!(BitmapFont::parse(&data, string));
+        let glyph_height = |id: char| {
+            bitmap_font.chars.iter().find(|c| c.id == id as u32).map_or(0.0, |c| c.size.1 as f32)
+        };
+        let metrics = FontMetrics {
+            ascent: bitmap_font.base,
+            descent: bitmap_font.base - bitmap_font.line_height,
+            line_gap: 0.0,
+            line_height: bitmap_font.line_height,
+            x_height: glyph_height('x'),
+            cap_height: glyph_height('H'),
+        };
+        // bitmap glyphs are never evicted, they only have to be copied again after the font was
+        // unloaded
+        let face = self.glyph_cache.borrow().get_face(string);
+        if let Some(face) = face {
+            let glyph_count = self.glyph_cache.borrow().get_glyph_count(face, bitmap_font.size);
+            if glyph_count > 0 {
+                return Ok(self.register_font(string, face, bitmap_font.size, GlyphMode::Bitmap, metrics, None));
+            }
+        }
+
+        let directory = match string.rfind('/') {
+            Some(i) => &string[..i + 1],
+            None => "",
+        };
+
+        let mut pages = Vec::new();
+        for page in &bitmap_font.pages {
+            let page_data = try!(self.read_asset(&format!("{}{}", directory, page)));
+            let img = try!(image::load_from_memory(&page_data));
+            let has_alpha = match img.color() {
+                image::ColorType::RGBA(_) | image::ColorType::GrayA(_) => true,
+                _ => false,
+            };
+            let (width, height) = img.dimensions();
+            let coverage: Vec<u8> = img.to_rgba().pixels().map(|p| {
+                match has_alpha {
+                    true => p[3],
+                    false => ((p[0] as u32 + p[1] as u32 + p[2] as u32) / 3) as u8,
+                }
+            }).collect();
+            pages.push((width as usize, height as usize, coverage));
+        }
+
+        let face = {
+            let mut glyph_cache = self.glyph_cache.borrow_mut();
+            let face = match face {
+                Some(face) => face,
+                None => glyph_cache.add_bitmap_face(string),
+            };
+            for c in &bitmap_font.chars {
+                let character = match ::std::char::from_u32(c.id) {
+                    Some(character) => character,
+                    None => continue,
+                };
+                let mut glyph = Glyph::new();
+                glyph.advance_x = c.advance_x;
+                let (width, height) = (c.size.0 as usize, c.size.1 as usize);
+                let (page_width, page_height, ref coverage) = pages[c.page];
+                let (x, y) = (c.position.0 as usize, c.position.1 as usize);
+                let mut data: &[u8] = &[];
+                if x + width <= page_width && y + height <= page_height {
+                    glyph.width = width as f32;
+                    glyph.height = height as f32;
+                    // bitmap fonts measure offsets from the top of the line instead of the baseline
+                    glyph.offset = (c.offset.0, bitmap_font.base - c.offset.1);
+                    data = &coverage[x + y * page_width..];
+                }
+                try!(glyph_cache.insert_bitmap_glyph(face, bitmap_font.size, character, glyph, data, page_width));
+            }
+            for &(first, second, amount) in &bitmap_font.kernings {
+                if let (Some(first), Some(second)) = (::std::char::from_u32(first), ::std::char::from_u32(second)) {
+                    glyph_cache.insert_bitmap_kerning(face, bitmap_font.size, first, second, amount);
+                }
+            }
+            face
+        };
+        Ok(self.register_font(string, face, bitmap_font.size, GlyphMode::Bitmap, metrics, None))
+    }
+
+    fn register_font(&mut self, string: &str, face: usize, size: f32, mode: GlyphMode, metrics: FontMetrics, modified: Option<SystemTime>) -> FontHandle {
+        let font_name = self.intern_string(&font_key(string, size as u32, mode));
+        if let Some(handle) = self.fonts.remove(&font_name.id) {
+            self.font_slots.remove(handle.index as usize, handle.generation);
+        }
 
-        Ok(Font {
+        let id = font_name.id;
+        let (index, generation) = self.font_slots.insert(FontInternal {
             name: font_name,
             path: string.to_string(),
-            atlas: font_atlas,
-            glyphs: glyphs,
-            kernings: kernings,
-            size: size as f32,
-        })
+            size: size,
+            mode: mode,
+            metrics: metrics,
+            face: face,
+            fallbacks: Vec::new(),
+            modified: modified,
+        });
+        let handle = FontHandle { index: index as u32, generation: generation };
+        self.fonts.insert(id, handle);
+        handle
     }
 
     /*pub fn upload_atlas(&mut self, frame: &glium::backend::glutin_backend::GlutinFacade, id: usize) -> Result<()> {
@@ -387,3 +1812,98 @@ impl ResourceManager {
         }
     }*/
 }
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use std::cell::RefCell;
+    use image;
+    use super::{ResourceManager, Texture, TextureAtlas, TextureHandle, TextureRegion, TextureTransform, StringId,
+                rotate_pixels};
+
+    fn handle() -> TextureHandle {
+        TextureHandle { index: 0, generation: 0, transform: TextureTransform::default() }
+    }
+
+    /// Where the corners of the shown texture come from in the upright image.
+    fn corners(handle: TextureHandle) -> Vec<(f32, f32)> {
+        let transform = handle.get_transform();
+        vec![transform.to_source(0.0, 0.0), transform.to_source(1.0, 0.0),
+             transform.to_source(0.0, 1.0), transform.to_source(1.0, 1.0)]
+    }
+
+    #[test]
+    fn quarter_turns_map_corners() {
+        let turned = handle().rotate_clockwise();
+        assert_eq!(corners(turned), vec![(0.0, 1.0), (0.0, 0.0), (1.0, 1.0), (1.0, 0.0)]);
+        assert!(turned.get_transform().is_turned_sideways());
+        assert_eq!(corners(handle().rotate_counter_clockwise()), corners(turned.rotate_clockwise().rotate_clockwise()));
+        assert_eq!(handle().rotate_clockwise().rotate_counter_clockwise(), handle());
+        assert_eq!(turned.rotate_clockwise().rotate_clockwise().rotate_clockwise(), handle());
+    }
+
+    #[test]
+    fn flips_compose_with_turns() {
+        assert_eq!(handle().flip_horizontal().flip_horizontal(), handle());
+        assert_eq!(corners(handle().flip_horizontal()), vec![(1.0, 0.0), (0.0, 0.0), (1.0, 1.0), (0.0, 1.0)]);
+        // flipping both ways is half a turn
+        assert_eq!(corners(handle().flip_horizontal().flip_vertical()),
+                   corners(handle().rotate_clockwise().rotate_clockwise()));
+        // a flip mirrors the texture as it is shown, also after a turn
+        let turned = handle().rotate_clockwise();
+        let flipped = turned.flip_horizontal();
+        for &(s, t) in &[(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)] {
+            assert_eq!(flipped.get_transform().to_source(s, t), turned.get_transform().to_source(1.0 - s, t));
+        }
+        let flipped = turned.flip_vertical();
+        for &(s, t) in &[(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)] {
+            assert_eq!(flipped.get_transform().to_source(s, t), turned.get_transform().to_source(s, 1.0 - t));
+        }
+    }
+
+    #[test]
+    fn rotated_pixels_are_turned_clockwise() {
+        let pixels: Vec<u8> = (0..6).flat_map(|i| vec![i; 4]).collect();
+        let rotated = rotate_pixels(&pixels, 3, 2);
+        let rotated: Vec<u8> = rotated.chunks(4).map(|pixel| pixel[0]).collect();
+        // a b c      d a
+        // d e f  ->  e b
+        //            f c
+        assert_eq!(rotated, vec![3, 0, 4, 1, 5, 2]);
+    }
+
+    #[test]
+    fn sub_rect_of_rotated_region() {
+        let atlas = Rc::new(RefCell::new(TextureAtlas::new(64, 64, 4)));
+        let mut region = TextureRegion::new(atlas.clone(), 8, 16, 4, 2);
+        region.rotated = true;
+        region.move_to(atlas, 8, 16, 4, 2);
+        let string_id_table = Rc::new(RefCell::new(::std::collections::HashMap::new()));
+        let texture = Texture {
+            name: StringId::new("image", Rc::downgrade(&string_id_table)),
+            region: Rc::new(RefCell::new(region)),
+            rect: Some((1, 0, 2, 1)),
+            transform: TextureTransform::default(),
+        };
+        // the upright 4x2 image takes up 2x4 pixels of the atlas
+        assert_eq!(texture.get_uv_min(), (9.0 / 64.0, 17.0 / 64.0));
+        assert_eq!(texture.get_uv_max(), (10.0 / 64.0, 19.0 / 64.0));
+        assert_eq!(texture.get_uv(0.0, 0.0), (10.0 / 64.0, 17.0 / 64.0));
+        assert_eq!(texture.get_size(), (2, 1));
+    }
+
+    #[test]
+    fn sub_texture_of_turned_handle() {
+        let mut resource_manager = ResourceManager::new();
+        let img = image::DynamicImage::ImageRgba8(image::ImageBuffer::new(4, 2));
+        let texture = resource_manager.create_texture_from_image("image", img).unwrap().rotate_clockwise();
+        assert_eq!(resource_manager.resolve_texture(texture).get_size(), (2, 4));
+        // the top row as shown is the left column of the upright image
+        let sub_texture = resource_manager.create_sub_texture(texture, 0, 0, 2, 1).unwrap();
+        assert_eq!(sub_texture.get_transform(), texture.get_transform());
+        let resolved = resource_manager.resolve_texture(sub_texture);
+        assert_eq!(resolved.name.get_name(), "image#0,0,1,2");
+        assert_eq!(resolved.get_size(), (2, 1));
+        assert!(resource_manager.create_sub_texture(texture, 1, 0, 2, 1).is_none());
+    }
+}
@@ -0,0 +1,109 @@
+/// Storage for resources that are referred to by generational handles. Removing a value bumps the
+/// generation of its slot, so old handles stop resolving even after the slot is reused.
+pub struct Slots<T> {
+    entries: Vec<(u32, Option<T>)>,
+    free: Vec<usize>,
+}
+
+impl<T> Slots<T> {
+    pub fn new() -> Slots<T> {
+        Slots {
+            entries: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+
+    /// Stores `value` and returns the index and generation of its slot.
+    pub fn insert(&mut self, value: T) -> (usize, u32) {
+        match self.free.pop() {
+            Some(index) => {
+                let entry = &mut self.entries[index];
+                entry.1 = Some(value);
+                (index, entry.0)
+            }
+            None => {
+                self.entries.push((0, Some(value)));
+                (self.entries.len() - 1, 0)
+            }
+        }
+    }
+
+    pub fn get(&self, index: usize, generation: u32) -> Option<&T> {
+        match self.entries.get(index) {
+            Some(&(entry_generation, Some(ref value))) if entry_generation == generation => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, index: usize, generation: u32) -> Option<&mut T> {
+        match self.entries.get_mut(index) {
+            Some(&mut (entry_generation, Some(ref mut value))) if entry_generation == generation => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn remove(&mut self, index: usize, generation: u32) -> Option<T> {
+        if self.get(index, generation).is_none() {
+            return None;
+        }
+        let entry = &mut self.entries[index];
+        entry.0 = entry.0.wrapping_add(1);
+        self.free.push(index);
+        entry.1.take()
+    }
+
+    /// Iterates over the index, generation and value of every occupied slot.
+    pub fn iter<'a>(&'a self) -> Box<Iterator<Item = (usize, u32, &'a T)> + 'a> {
+        Box::new(self.entries.iter().enumerate().filter_map(|(index, &(generation, ref value))| {
+            value.as_ref().map(|value| (index, generation, value))
+        }))
+    }
+
+    pub fn values_mut<'a>(&'a mut self) -> Box<Iterator<Item = &'a mut T> + 'a> {
+        Box::new(self.entries.iter_mut().filter_map(|&mut (_, ref mut value)| value.as_mut()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Slots;
+
+    #[test]
+    fn removed_value_stops_resolving() {
+        let mut slots = Slots::new();
+        let (index, generation) = slots.insert("a");
+        assert_eq!(slots.get(index, generation), Some(&"a"));
+        assert_eq!(slots.remove(index, generation), Some("a"));
+        assert_eq!(slots.get(index, generation), None);
+        assert_eq!(slots.remove(index, generation), None);
+    }
+
+    #[test]
+    fn reused_slot_rejects_stale_generation() {
+        let mut slots = Slots::new();
+        let (index, generation) = slots.insert("a");
+        slots.remove(index, generation);
+        let (new_index, new_generation) = slots.insert("b");
+        assert_eq!(new_index, index);
+        assert!(new_generation != generation);
+        assert_eq!(slots.get(index, generation), None);
+        assert!(slots.get_mut(index, generation).is_none());
+        assert_eq!(slots.remove(index, generation), None);
+        assert_eq!(slots.get(new_index, new_generation), Some(&"b"));
+    }
+
+    #[test]
+    fn iter_skips_free_slots() {
+        let mut slots = Slots::new();
+        let a = slots.insert(1);
+        let b = slots.insert(2);
+        slots.insert(3);
+        slots.remove(b.0, b.1);
+        let values: Vec<(usize, i32)> = slots.iter().map(|(index, _, value)| (index, *value)).collect();
+        assert_eq!(values, vec![(a.0, 1), (2, 3)]);
+        for value in slots.values_mut() {
+            *value *= 10;
+        }
+        assert_eq!(slots.get(a.0, a.1), Some(&10));
+    }
+}
@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::str;
+use rustc_serialize::json::Json;
+use super::resource_manager::{Result, ResourceManagerError};
+
+/// Duration of frames that don't specify one, e.g. in TexturePacker exports.
+pub const DEFAULT_FRAME_DURATION: f32 = 0.1;
+
+/// A frame of a sprite sheet in pixels of the sheet image. `duration` is in seconds.
+#[derive(Debug, Clone)]
+pub struct SheetFrame {
+    pub name: String,
+    pub position: (u32, u32),
+    pub size: (u32, u32),
+    /// Position of the frame within the sprite before transparent borders were trimmed.
+    pub source_offset: (u32, u32),
+    /// Size of the sprite before transparent borders were trimmed.
+    pub source_size: (u32, u32),
+    pub duration: f32,
+}
+
+/// Layout of a sprite sheet, either a grid or read from a JSON export of Aseprite or
+/// TexturePacker.
+#[derive(Debug)]
+pub struct SheetLayout {
+    /// Image file of the sheet relative to the JSON file, `None` for grids.
+    pub image: Option<String>,
+    pub frames: Vec<SheetFrame>,
+    /// Aseprite frame tags as inclusive ranges of frame indices.
+    pub tags: HashMap<String, (usize, usize)>,
+}
+
+impl SheetLayout {
+    /// Cuts an image of `width` x `height` pixels into cells of `frame_width` x `frame_height`,
+    /// row by row. Cells that are cut off at the right or bottom edge are skipped.
+    pub fn grid(width: u32, height: u32, frame_width: u32, frame_height: u32, duration: f32) -> SheetLayout {
+        let mut frames = Vec::new();
+        if frame_width > 0 && frame_height > 0 {
+            for row in 0..height / frame_height {
+                for column in 0..width / frame_width {
+                    let index = frames.len();
+                    frames.push(SheetFrame {
+                        name: index.to_string(),
+                        position: (column * frame_width, row * frame_height),
+                        size: (frame_width, frame_height),
+                        source_offset: (0, 0),
+                        source_size: (frame_width, frame_height),
+                        duration: duration,
+                    });
+                }
+            }
+        }
+        SheetLayout {
+            image: None,
+            frames: frames,
+            tags: HashMap::new(),
+        }
+    }
+
+    /// Parses the JSON of an Aseprite or TexturePacker export in the hash or the array format,
+    /// `name` is only used for error messages. Frames of the hash format are ordered by their
+    /// names with numbers compared by value, since the JSON object doesn't keep the file order.
+    pub fn parse_json(data: &[u8], name: &str) -> Result<SheetLayout> {
+        let invalid = |reason: &str| ResourceManagerError::InvalidManifest(format!("{}: {}", name, reason));
+        let text = try!(str::from_utf8(data).map_err(|_| invalid("not UTF-8")));
+        let json = try!(Json::from_str(text).map_err(|err| invalid(&err.to_string())));
+
+        let mut entries: Vec<(String, &Json)> = Vec::new();
+        match json.find("frames") {
+            Some(&Json::Object(ref frames)) => {
+                for (frame_name, frame) in frames {
+                    entries.push((frame_name.clone(), frame));
+                }
+                entries.sort_by(|a, b| natural_cmp(&a.0, &b.0));
+            }
+            Some(&Json::Array(ref frames)) => {
+                for frame in frames {
+                    let frame_name = match frame.find("filename").and_then(|filename| filename.as_string()) {
+                        Some(frame_name) => frame_name.to_string(),
+                        None => entries.len().to_string(),
+                    };
+                    entries.push((frame_name, frame));
+                }
+            }
+            _ => return Err(invalid("no frames")),
+        }
+
+        let mut frames = Vec::new();
+        for (frame_name, frame) in entries {
+            if frame.find("rotated").and_then(|rotated| rotated.as_boolean()) == Some(true) {
+                return Err(invalid(&format!("frame {} is rotated, rotated frames aren't supported", frame_name)));
+            }
+            let number = |object: &str, key: &str| -> Result<u32> {
+                match frame.find(object).and_then(|object| object.find(key)).and_then(|value| value.as_u64()) {
+                    Some(value) if value <= u32::max_value() as u64 => Ok(value as u32),
+                    _ => Err(invalid(&format!("frame {} has no {}.{}", frame_name, object, key))),
+                }
+            };
+            let position = (try!(number("frame", "x")), try!(number("frame", "y")));
+            let size = (try!(number("frame", "w")), try!(number("frame", "h")));
+            // trimmed frames are placed within the untrimmed sprite, both exporters write these
+            // fields for untrimmed frames as well
+            let (source_offset, source_size) = if frame.find("spriteSourceSize").is_some() {
+                let source_offset = (try!(number("spriteSourceSize", "x")), try!(number("spriteSourceSize", "y")));
+                let source_size = (try!(number("sourceSize", "w")), try!(number("sourceSize", "h")));
+                let fits = |offset: u32, size: u32, source_size: u32| {
+                    offset.checked_add(size).map_or(false, |end| end <= source_size)
+                };
+                if !fits(source_offset.0, size.0, source_size.0) || !fits(source_offset.1, size.1, source_size.1) {
+                    return Err(invalid(&format!("frame {} is outside of its source size", frame_name)));
+                }
+                (source_offset, source_size)
+            } else {
+                ((0, 0), size)
+            };
+            // Aseprite stores durations in milliseconds
+            let duration = frame.find("duration").and_then(|duration| duration.as_f64())
+                                 .map_or(DEFAULT_FRAME_DURATION, |duration| duration as f32 / 1000.0);
+            frames.push(SheetFrame {
+                name: frame_name,
+                position: position,
+                size: size,
+                source_offset: source_offset,
+                source_size: source_size,
+                duration: duration,
+            });
+        }
+
+        let mut tags = HashMap::new();
+        if let Some(frame_tags) = json.find_path(&["meta", "frameTags"]).and_then(|frame_tags| frame_tags.as_array()) {
+            for tag in frame_tags {
+                let tag_name = tag.find("name").and_then(|tag_name| tag_name.as_string());
+                let from = tag.find("from").and_then(|from| from.as_u64());
+                let to = tag.find("to").and_then(|to| to.as_u64());
+                match (tag_name, from, to) {
+                    (Some(tag_name), Some(from), Some(to)) if from <= to && (to as usize) < frames.len() => {
+                        tags.insert(tag_name.to_string(), (from as usize, to as usize));
+                    }
+                    _ => return Err(invalid("invalid frame tag")),
+                }
+            }
+        }
+
+        Ok(SheetLayout {
+            image: json.find_path(&["meta", "image"]).and_then(|image| image.as_string()).map(|image| image.to_string()),
+            frames: frames,
+            tags: tags,
+        })
+    }
+}
+
+/// Compares strings with runs of digits compared by their value, so `walk 2` comes before
+/// `walk 10`.
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        match (a.chars().next(), b.chars().next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_digit(10) && y.is_digit(10) => {
+                let a_end = a.find(|c: char| !c.is_digit(10)).unwrap_or(a.len());
+                let b_end = b.find(|c: char| !c.is_digit(10)).unwrap_or(b.len());
+                let (a_digits, b_digits) = (a[..a_end].trim_left_matches('0'), b[..b_end].trim_left_matches('0'));
+                let ordering = match a_digits.len().cmp(&b_digits.len()) {
+                    Ordering::Equal => a_digits.cmp(b_digits),
+                    ordering => ordering,
+                };
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a = &a[a_end..];
+                b = &b[b_end..];
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                a = &a[x.len_utf8()..];
+                b = &b[y.len_utf8()..];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+    use super::{SheetLayout, natural_cmp};
+    use resources::{ResourceManager, MemorySource};
+
+    const HASH_SHEET: &'static str = r#"{
+        "frames": {
+            "walk 10": { "frame": { "x": 0, "y": 0, "w": 4, "h": 4 }, "duration": 50 },
+            "walk 2": {
+                "frame": { "x": 4, "y": 0, "w": 5, "h": 6 },
+                "rotated": false,
+                "trimmed": true,
+                "spriteSourceSize": { "x": 2, "y": 3, "w": 5, "h": 6 },
+                "sourceSize": { "w": 11, "h": 12 }
+            }
+        },
+        "meta": {
+            "image": "sheet.png",
+            "frameTags": [ { "name": "walk", "from": 0, "to": 1 } ]
+        }
+    }"#;
+
+    #[test]
+    fn numbers_compare_by_value() {
+        assert_eq!(natural_cmp("walk 2", "walk 10"), Ordering::Less);
+        assert_eq!(natural_cmp("walk 010", "walk 9"), Ordering::Greater);
+        assert_eq!(natural_cmp("walk 02", "walk 2"), Ordering::Equal);
+        assert_eq!(natural_cmp("run 2", "walk 1"), Ordering::Less);
+        assert_eq!(natural_cmp("walk", "walk 1"), Ordering::Less);
+    }
+
+    #[test]
+    fn grid_skips_cut_off_cells() {
+        let layout = SheetLayout::grid(10, 7, 4, 3, 0.5);
+        let positions: Vec<(u32, u32)> = layout.frames.iter().map(|frame| frame.position).collect();
+        assert_eq!(positions, vec![(0, 0), (4, 0), (0, 3), (4, 3)]);
+        assert_eq!(layout.frames[3].name, "3");
+        assert_eq!(layout.frames[3].source_size, (4, 3));
+    }
+
+    #[test]
+    fn hash_frames_are_ordered_by_name() {
+        let layout = SheetLayout::parse_json(HASH_SHEET.as_bytes(), "sheet.json").unwrap();
+        assert_eq!(layout.image, Some("sheet.png".to_string()));
+        let names: Vec<&str> = layout.frames.iter().map(|frame| &frame.name[..]).collect();
+        assert_eq!(names, vec!["walk 2", "walk 10"]);
+        assert_eq!(layout.frames[0].duration, super::DEFAULT_FRAME_DURATION);
+        assert_eq!(layout.frames[1].duration, 0.05);
+        assert_eq!(layout.tags["walk"], (0, 1));
+    }
+
+    #[test]
+    fn trimmed_frame_keeps_offset_within_source_size() {
+        let layout = SheetLayout::parse_json(HASH_SHEET.as_bytes(), "sheet.json").unwrap();
+        let trimmed = &layout.frames[0];
+        assert_eq!((trimmed.position, trimmed.size), ((4, 0), (5, 6)));
+        assert_eq!((trimmed.source_offset, trimmed.source_size), ((2, 3), (11, 12)));
+        let untrimmed = &layout.frames[1];
+        assert_eq!((untrimmed.source_offset, untrimmed.source_size), ((0, 0), (4, 4)));
+    }
+
+    #[test]
+    fn trimmed_frame_outside_of_source_size_is_invalid() {
+        let json = HASH_SHEET.replace(r#""x": 2, "y": 3"#, r#""x": 7, "y": 3"#);
+        assert!(SheetLayout::parse_json(json.as_bytes(), "sheet.json").is_err());
+    }
+
+    #[test]
+    fn array_frames_keep_file_order() {
+        let json = r#"{ "frames": [
+            { "filename": "b", "frame": { "x": 0, "y": 0, "w": 1, "h": 1 } },
+            { "filename": "a", "frame": { "x": 1, "y": 0, "w": 1, "h": 1 } }
+        ] }"#;
+        let layout = SheetLayout::parse_json(json.as_bytes(), "sheet.json").unwrap();
+        let names: Vec<&str> = layout.frames.iter().map(|frame| &frame.name[..]).collect();
+        assert_eq!(names, vec!["b", "a"]);
+        assert_eq!(layout.image, None);
+    }
+
+    #[test]
+    fn sprite_frames_carry_trim_offset() {
+        let mut source = MemorySource::new();
+        source.insert("sprites/sheet.json", HASH_SHEET.as_bytes().to_vec());
+        source.insert("sprites/sheet.png", include_bytes!("../../example_images/dock.png").to_vec());
+        let mut resource_manager = ResourceManager::new();
+        resource_manager.clear_asset_sources();
+        resource_manager.add_asset_source(Box::new(source));
+        let sheet = resource_manager.create_sprite_sheet("sprites/sheet.json").unwrap();
+        assert_eq!(sheet.frames[0].offset, (2.0, 3.0));
+        assert_eq!(sheet.frames[0].source_size, (11.0, 12.0));
+        assert_eq!(sheet.frames[1].offset, (0.0, 0.0));
+        assert_eq!(sheet.get_animation("walk").map(|frames| frames.len()), Some(2));
+    }
+}
@@ -1,49 +1,71 @@
-use std::usize;
 use std::ptr;
+use std::cmp;
+use std::io;
 use std::path::Path;
 use image;
 use glium;
 use glium::texture::Texture2d;
+use super::packing::{Packer, PackerStats, PackingStrategy};
 
 pub struct TextureAtlas {
     width: usize,
     height: usize,
     depth: usize,
-    skyline: Vec<(usize, usize, usize)>,
-    used: usize,
+    packer: Box<Packer>,
+    gutter: usize,
+    /// Regions of the packer including their gutter, together with the gutter they were
+    /// allocated with.
+    regions: Vec<((usize, usize, usize, usize), usize)>,
     data: Vec<u8>,
+    mipmaps: bool,
+    mip_levels: Vec<Vec<u8>>,
 
     texture: Option<Texture2d>,
     needs_upload: bool,
+    dirty_regions: Vec<(usize, usize, usize, usize)>,
 }
 
 impl TextureAtlas {
     pub fn new(width: usize, height: usize, depth: usize) -> Self {
+        TextureAtlas::new_with_strategy(width, height, depth, PackingStrategy::Skyline)
+    }
+
+    pub fn new_with_strategy(width: usize, height: usize, depth: usize, strategy: PackingStrategy) -> Self {
+        TextureAtlas::new_with_packer(width, height, depth, strategy.create_packer(width, height))
+    }
+
+    pub fn new_with_packer(width: usize, height: usize, depth: usize, packer: Box<Packer>) -> Self {
         let mut result = TextureAtlas {
             width: width,
             height: height,
             depth: depth,
-            skyline: Vec::new(),
-            used: 0,
+            packer: packer,
+            gutter: 0,
+            regions: Vec::new(),
             data: Vec::with_capacity(width * height * depth),
+            mipmaps: false,
+            mip_levels: Vec::new(),
             texture: None,
             needs_upload: true,
+            dirty_regions: Vec::new(),
             //data: vec![0; width * height * depth]
         };
-        result.skyline.push((1, 1, width - 2));
         for _ in 0..result.data.capacity() {
             result.data.push(0);
         }
+        result
+    }
 
-        /*result.skyline.push((1, 1, 5));
-        result.skyline.push((6, 3, 5));
-        result.skyline.push((11, 3, 5));
-        result.skyline.push((16, 2, 5));
-        result.skyline.push((21, 3, 5));*/
+    /// Creates an atlas from existing pixel data, e.g. a baked atlas page. The packer starts out
+    /// empty, so regions should not be requested from such an atlas.
+    pub fn new_with_data(width: usize, height: usize, depth: usize, data: Vec<u8>) -> Self {
+        let mut result = TextureAtlas::new(width, height, depth);
+        assert_eq!(data.len(), width * height * depth);
+        result.data = data;
         result
     }
 
-    pub fn set_region(&mut self, x: usize, y: usize, width: usize, height: usize, data: &Vec<u8>, stride: usize) {
+    pub fn set_region(&mut self, x: usize, y: usize, width: usize, height: usize, data: &[u8], stride: usize) {
         // TODO: checks
 
         for i in 0..height {
@@ -51,147 +73,318 @@ impl TextureAtlas {
                 ptr::copy_nonoverlapping(&data[i * stride], &mut self.data[((y + i) * self.width + x) * self.depth], width * self.depth);
             }
         }
-        self.needs_upload = true;
+        // regions keep the gutter they were allocated with, pixels outside of regions (e.g. in a
+        // baked atlas) have none
+        let gutter = self.get_region_gutter(x, y);
+        if gutter > 0 {
+            self.extrude(x, y, width, height, gutter);
+        }
+        if !self.needs_upload {
+            self.dirty_regions.push((x - gutter, y - gutter, width + 2 * gutter, height + 2 * gutter));
+        }
     }
 
-    fn fit(&self, index: usize, width: usize, height: usize) -> Option<usize> {
-        let (x, mut y, _) = self.skyline[index];
-        let mut width_left: i32 = width as i32;
-        let mut i = index;
-
-        if x + width > self.width - 1 {
-            //return Err("Not enough atlas width left!");
-            return None;
+    /// Sets the number of pixels that is kept free around each region. The border pixels of a
+    /// region are extruded into its gutter so linear filtering doesn't pick up neighbouring regions.
+    /// Only affects regions that are allocated afterwards, existing regions keep their gutter.
+    pub fn set_gutter(&mut self, gutter: usize) {
+        // a smaller gutter allows fewer mip levels, see `get_mip_level_count`
+        if self.mipmaps && gutter < self.gutter {
+            self.needs_upload = true;
         }
-        while width_left > 0 {
-            let (_, cur_y, cur_width) = self.skyline[i];
-            if cur_y > y {
-                y = cur_y;
-            }
+        self.gutter = gutter;
+    }
 
-            if y + height > self.height - 1 {
-                //return Err("Not enough atlas height left!")
-                return None;
-            }
+    pub fn get_gutter(&self) -> usize {
+        self.gutter
+    }
+
+    fn find_region(&self, x: usize, y: usize) -> Option<usize> {
+        self.regions.iter().position(|&((region_x, region_y, width, height), _)| {
+            x >= region_x && y >= region_y && x < region_x + width && y < region_y + height
+        })
+    }
 
-            width_left -= cur_width as i32;
-            i += 1;
+    fn get_region_gutter(&self, x: usize, y: usize) -> usize {
+        match self.find_region(x, y) {
+            Some(index) => self.regions[index].1,
+            None => 0,
         }
-        //Ok(y)
-        Some(y)
     }
 
-    fn merge(&mut self) {
-        let mut to_remove = Vec::<usize>::new();
-        let mut last = 0;
+    fn copy_pixels(&mut self, from: (usize, usize), to: (usize, usize), count: usize) {
+        let src = (from.1 * self.width + from.0) * self.depth;
+        let dst = (to.1 * self.width + to.0) * self.depth;
+        unsafe {
+            let data = self.data.as_mut_ptr();
+            ptr::copy_nonoverlapping(data.offset(src as isize), data.offset(dst as isize), count * self.depth);
+        }
+    }
 
-        for i in 1..self.skyline.len() {
-            if self.skyline[i].1 == self.skyline[last].1 {
-                self.skyline[last].2 += self.skyline[i].2;
-                to_remove.push(i);
-            }
-            else {
-                last = i;
+    fn extrude(&mut self, x: usize, y: usize, width: usize, height: usize, gutter: usize) {
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        for row in y..y + height {
+            for i in 1..gutter + 1 {
+                self.copy_pixels((x, row), (x - i, row), 1);
+                self.copy_pixels((x + width - 1, row), (x + width - 1 + i, row), 1);
             }
         }
+        // whole rows including the columns extruded above, this also fills the corners
+        for i in 1..gutter + 1 {
+            self.copy_pixels((x - gutter, y), (x - gutter, y - i), width + 2 * gutter);
+            self.copy_pixels((x - gutter, y + height - 1), (x - gutter, y + height - 1 + i), width + 2 * gutter);
+        }
+    }
 
-        for i in to_remove.iter().rev() {
-            self.skyline.remove(*i);
+    pub fn get_region_data(&self, x: usize, y: usize, width: usize, height: usize) -> Vec<u8> {
+        let mut result = Vec::with_capacity(width * height * self.depth);
+        for i in 0..height {
+            let start = ((y + i) * self.width + x) * self.depth;
+            result.extend_from_slice(&self.data[start..start + width * self.depth]);
         }
+        result
+    }
 
-        /*for &(x, y, width) in &self.skyline {
-            println!("{} {} {}", x, y, width);
-        }*/
+    pub fn get_dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
     }
 
     pub fn get_region(&mut self, width: usize, height: usize) -> Option<(usize, usize, usize, usize)> {
-        let mut region = (0, 0, width, height);
-        let (mut best_height, mut best_width, mut best_index) = (usize::MAX, usize::MAX, usize::MAX);
-
-        let mut i = 0;
-        for &(cur_x, _, cur_width) in &self.skyline {
-            if let Some(y) = self.fit(i, width, height) {
-                if (y + height < best_height) || (y + height == best_height && cur_width < best_width) {
-                    best_height = y + height;
-                    best_index = i;
-                    best_width = cur_width;
-                    region.0 = cur_x;
-                    region.1 = y;
-                }
+        let gutter = self.gutter;
+        let region = match self.packer.get_region(width + 2 * gutter, height + 2 * gutter) {
+            Some(region) => region,
+            None => return None,
+        };
+        self.regions.push((region, gutter));
+        Some((region.0 + gutter, region.1 + gutter, width, height))
+    }
+
+    /// Gives the region at `(x, y)` that was returned by `get_region` back to the packer, together
+    /// with its gutter. Returns `false` if there is no such region, e.g. in a baked atlas.
+    pub fn free_region(&mut self, x: usize, y: usize) -> bool {
+        match self.find_region(x, y) {
+            Some(index) => {
+                let (region, _) = self.regions.swap_remove(index);
+                self.packer.free_region(region);
+                true
             }
-            i += 1;
+            None => false,
         }
+    }
+
+    pub fn get_stats(&self) -> PackerStats {
+        self.packer.get_stats()
+    }
 
-        if best_index == usize::MAX {
-            //return Err("Couldn't fit region into atlas!");
-            return None;
+    /// Bytes of pixel data kept in main memory, including the mip chain once it was built.
+    pub fn get_cpu_bytes(&self) -> usize {
+        self.data.len() + self.mip_levels.iter().map(|level| level.len()).sum::<usize>()
+    }
+
+    /// Bytes the atlas takes up in main memory once its mip chain is built, which happens on
+    /// the first upload.
+    pub fn get_reserved_bytes(&self) -> usize {
+        if !self.mipmaps {
+            return self.data.len();
         }
+        self.data.len() + (1..self.get_mip_level_count() + 1).map(|level| {
+            let (width, height) = self.get_level_dimensions(level);
+            width * height * self.depth
+        }).sum::<usize>()
+    }
 
-        self.skyline.insert(best_index, (region.0, region.1 + height, width));
+    /// Bytes of the texture on the GPU, 0 before the first upload.
+    pub fn get_gpu_bytes(&self) -> usize {
+        let levels = match self.texture {
+            Some(ref texture) => texture.get_mipmap_levels(),
+            None => return 0,
+        };
+        (0..levels).map(|level| {
+            let (width, height) = self.get_level_dimensions(level);
+            width * height * self.depth
+        }).sum()
+    }
 
-        let mut to_remove = Vec::<usize>::new();
+    pub fn clear(&mut self) {
+        self.packer.clear();
 
-        for i in best_index + 1..self.skyline.len() {
-            if self.skyline[i].0 < region.0 + region.2 {
-                let shrink = region.0 + region.2 - self.skyline[i].0;
-                if shrink >= self.skyline[i].2 {
-                    to_remove.push(i);
-                }
-                else {
-                    self.skyline[i].0 += shrink;
-                    self.skyline[i].2 -= shrink;
-                    break;
-                }
-            }
-            else {
-                break;
-            }
+        for i in &mut self.data {
+            *i = 0;
         }
+        self.regions.clear();
+        self.mip_levels.clear();
+        self.needs_upload = true;
+        self.dirty_regions.clear();
+    }
 
-        for i in to_remove.iter().rev() {
-            self.skyline.remove(*i);
+    /// Enables a mip chain for the atlas. Every level is built region by region and the chain
+    /// stops at the last level where `2^level` fits into the gutter of every region, so regions
+    /// never bleed into each other. With a gutter of 2 pixels that is a single level below the
+    /// full resolution, a gutter below 2 pixels doesn't allow any.
+    pub fn set_mipmaps(&mut self, mipmaps: bool) {
+        if self.mipmaps != mipmaps {
+            self.mipmaps = mipmaps;
+            self.mip_levels.clear();
+            self.needs_upload = true;
         }
+    }
 
-        self.merge();
-        self.used += width * height;
-        Some(region)
+    pub fn has_mipmaps(&self) -> bool {
+        self.mipmaps
     }
 
-    pub fn clear(&mut self) {
-        self.skyline.clear();
-        self.skyline.push((1, 1, self.width - 2));
-        self.used = 0;
+    /// Number of levels below the full resolution. Region rects are rounded outwards on every
+    /// level, so a level only stays inside of the gutters while `2^level` is at most the
+    /// smallest gutter.
+    fn get_mip_level_count(&self) -> u32 {
+        let gutter = self.regions.iter().fold(self.gutter, |gutter, &(_, region_gutter)| cmp::min(gutter, region_gutter));
+        let mut count = 0;
+        let mut size = if self.width > self.height { self.width } else { self.height };
+        while size > 1 && (2 << count) <= gutter {
+            size /= 2;
+            count += 1;
+        }
+        count
+    }
 
-        for i in &mut self.data {
-            *i = 0;
+    fn get_level_dimensions(&self, level: u32) -> (usize, usize) {
+        let width = self.width >> level;
+        let height = self.height >> level;
+        (if width == 0 { 1 } else { width }, if height == 0 { 1 } else { height })
+    }
+
+    fn get_level_rect(&self, level: u32, rect: (usize, usize, usize, usize)) -> (usize, usize, usize, usize) {
+        let (level_width, level_height) = self.get_level_dimensions(level);
+        let scale = 1 << level;
+        let (x, y) = (rect.0 >> level, rect.1 >> level);
+        let mut right = (rect.0 + rect.2 + scale - 1) >> level;
+        let mut bottom = (rect.1 + rect.3 + scale - 1) >> level;
+        if right > level_width {
+            right = level_width;
         }
+        if bottom > level_height {
+            bottom = level_height;
+        }
+        (x, y, right - x, bottom - y)
     }
 
-    pub fn upload(&mut self, frame: &glium::backend::glutin_backend::GlutinFacade) {
+    fn generate_mip_levels(&mut self, rect: (usize, usize, usize, usize)) {
+        let level_count = self.get_mip_level_count();
+        while self.mip_levels.len() < level_count as usize {
+            let (width, height) = self.get_level_dimensions(self.mip_levels.len() as u32 + 1);
+            self.mip_levels.push(vec![0; width * height * self.depth]);
+        }
+
+        for level in 1..level_count + 1 {
+            let src_size = self.get_level_dimensions(level - 1);
+            let dst_size = self.get_level_dimensions(level);
+            let src_rect = self.get_level_rect(level - 1, rect);
+            let dst_rect = self.get_level_rect(level, rect);
+            let depth = self.depth;
+            if level == 1 {
+                downsample(&self.data, src_size, src_rect, &mut self.mip_levels[0], dst_size, dst_rect, depth);
+            } else {
+                let (lower, upper) = self.mip_levels.split_at_mut(level as usize - 1);
+                downsample(&lower[level as usize - 2], src_size, src_rect, &mut upper[0], dst_size, dst_rect, depth);
+            }
+        }
+    }
+
+    fn get_raw_image(&self, level: u32, x: usize, y: usize, width: usize, height: usize) -> glium::texture::RawImage2d<'static, u8> {
         use std::borrow::Cow;
 
-        if self.needs_upload {
-            if self.depth == 4 {
-                let image = glium::texture::RawImage2d::from_raw_rgba(self.data.clone(), (self.width as u32, self.height as u32));
-                let texture = glium::texture::Texture2d::new(frame, image).unwrap();
-                self.texture = Some(texture);
-            } else if self.depth == 1 {
-                let mut buf = Vec::<glium::texture::RawImage1d<u8>>::new();
-                for i in 0..self.height {
-                    let row_data = Cow::Borrowed(&self.data[i * self.width..(i + 1) * self.width]);
-                    let row = glium::texture::RawImage1d {
-                        data: row_data,
-                        width: self.width as u32,
-                        format: glium::texture::ClientFormat::U8,
+        let data = if level == 0 {
+            self.get_region_data(x, y, width, height)
+        } else {
+            let level_width = self.get_level_dimensions(level).0;
+            let level_data = &self.mip_levels[level as usize - 1];
+            let mut result = Vec::with_capacity(width * height * self.depth);
+            for i in 0..height {
+                let start = ((y + i) * level_width + x) * self.depth;
+                result.extend_from_slice(&level_data[start..start + width * self.depth]);
+            }
+            result
+        };
+
+        let format = match self.depth {
+            1 => glium::texture::ClientFormat::U8,
+            _ => glium::texture::ClientFormat::U8U8U8U8,
+        };
+        glium::texture::RawImage2d {
+            data: Cow::Owned(data),
+            width: width as u32,
+            height: height as u32,
+            format: format,
+        }
+    }
+
+    /// Uploads the atlas to the GPU. The texture is only created on the first upload (or after
+    /// `clear`), afterwards only the rectangles that were changed with `set_region` since the last
+    /// upload are written. Without changes it does nothing, so draw batches call it every time
+    /// they create their buffers.
+    pub fn upload(&mut self, frame: &glium::backend::glutin_backend::GlutinFacade) {
+        // later updates write the levels the texture was created with, freeing regions can
+        // raise the level count of the atlas in the meantime
+        let level_count = match self.texture {
+            Some(ref texture) if !self.needs_upload => texture.get_mipmap_levels() - 1,
+            _ => if self.mipmaps { self.get_mip_level_count() } else { 0 },
+        };
+
+        if self.needs_upload || self.texture.is_none() {
+            let image = self.get_raw_image(0, 0, 0, self.width, self.height);
+            let texture = if self.mipmaps {
+                for (region, _) in self.regions.clone() {
+                    self.generate_mip_levels(region);
+                }
+                let mipmaps = glium::texture::MipmapsOption::EmptyMipmapsMax(level_count);
+                let texture = glium::texture::Texture2d::with_mipmaps(frame, image, mipmaps).unwrap();
+                for level in 1..level_count + 1 {
+                    let (width, height) = self.get_level_dimensions(level);
+                    let rect = glium::Rect {
+                        left: 0,
+                        bottom: 0,
+                        width: width as u32,
+                        height: height as u32,
                     };
-                    buf.push(row);
+                    texture.mipmap(level).unwrap().write(rect, self.get_raw_image(level, 0, 0, width, height));
                 }
-                let image = glium::texture::RawImage2d::from_vec_raw1d(&buf);
-                let texture = glium::texture::Texture2d::new(frame, image).unwrap();
-                self.texture = Some(texture);
-            }
+                texture
+            } else {
+                glium::texture::Texture2d::new(frame, image).unwrap()
+            };
+            self.texture = Some(texture);
             self.needs_upload = false;
+            self.dirty_regions.clear();
+            return;
+        }
+
+        if self.mipmaps {
+            for region in self.dirty_regions.clone() {
+                self.generate_mip_levels(region);
+            }
+        }
+
+        if let Some(ref texture) = self.texture {
+            for &region in &self.dirty_regions {
+                for level in 0..level_count + 1 {
+                    let (x, y, width, height) = self.get_level_rect(level, region);
+                    if width == 0 || height == 0 {
+                        continue;
+                    }
+                    let rect = glium::Rect {
+                        left: x as u32,
+                        bottom: y as u32,
+                        width: width as u32,
+                        height: height as u32,
+                    };
+                    texture.mipmap(level).unwrap().write(rect, self.get_raw_image(level, x, y, width, height));
+                }
+            }
         }
+        self.dirty_regions.clear();
     }
 
     pub fn get_texture(&self) -> Option<&Texture2d> {
@@ -203,17 +396,87 @@ impl TextureAtlas {
     }*/
 
     pub fn save_to_png(&self, i: usize) {
-        if self.depth == 4 {
-            let name = format!("atlas{}.png", i);
-            image::save_buffer(&Path::new(&*name), &self.data, self.width as u32, self.height as u32, image::RGBA(8)).unwrap();
+        let name = match self.depth {
+            1 => format!("font_atlas{}.png", i),
+            _ => format!("atlas{}.png", i),
+        };
+        self.save_to_file(&Path::new(&*name)).unwrap();
+    }
+
+    pub fn save_to_file(&self, path: &Path) -> io::Result<()> {
+        let color_type = match self.depth {
+            1 => image::Gray(8),
+            _ => image::RGBA(8),
+        };
+        image::save_buffer(path, &self.data, self.width as u32, self.height as u32, color_type)
+    }
+}
+
+/// Box filters `src_rect` of the source level into `dst_rect` of the next level. Source pixels
+/// outside of `src_rect` are clamped to its edge so neighbouring regions are never sampled.
+fn downsample(src: &[u8], src_size: (usize, usize), src_rect: (usize, usize, usize, usize),
+              dst: &mut [u8], dst_size: (usize, usize), dst_rect: (usize, usize, usize, usize), depth: usize) {
+    if src_rect.2 == 0 || src_rect.3 == 0 {
+        return;
+    }
+    let clamp = |value: usize, min: usize, size: usize| -> usize {
+        if value < min {
+            min
+        } else if value >= min + size {
+            min + size - 1
+        } else {
+            value
         }
-        else if self.depth == 1 {
-            let name = format!("font_atlas{}.png", i);
-            image::save_buffer(&Path::new(&*name), &self.data, self.width as u32, self.height as u32, image::Gray(8)).unwrap();
+    };
+
+    for y in dst_rect.1..dst_rect.1 + dst_rect.3 {
+        for x in dst_rect.0..dst_rect.0 + dst_rect.2 {
+            let x0 = clamp(x * 2, src_rect.0, src_rect.2);
+            let x1 = clamp(x * 2 + 1, src_rect.0, src_rect.2);
+            let y0 = clamp(y * 2, src_rect.1, src_rect.3);
+            let y1 = clamp(y * 2 + 1, src_rect.1, src_rect.3);
+            for c in 0..depth {
+                let sum = src[(y0 * src_size.0 + x0) * depth + c] as u32 +
+                          src[(y0 * src_size.0 + x1) * depth + c] as u32 +
+                          src[(y1 * src_size.0 + x0) * depth + c] as u32 +
+                          src[(y1 * src_size.0 + x1) * depth + c] as u32;
+                dst[(y * dst_size.0 + x) * depth + c] = ((sum + 2) / 4) as u8;
+            }
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TextureAtlas;
+
+    fn atlas_with_gutter(dimension: usize, gutter: usize) -> TextureAtlas {
+        let mut atlas = TextureAtlas::new(dimension, dimension, 4);
+        atlas.set_gutter(gutter);
+        atlas.set_mipmaps(true);
+        atlas
+    }
+
+    #[test]
+    fn mip_levels_fit_into_gutter() {
+        assert_eq!(atlas_with_gutter(2048, 0).get_mip_level_count(), 0);
+        assert_eq!(atlas_with_gutter(2048, 1).get_mip_level_count(), 0);
+        assert_eq!(atlas_with_gutter(2048, 2).get_mip_level_count(), 1);
+        assert_eq!(atlas_with_gutter(2048, 7).get_mip_level_count(), 2);
+        assert_eq!(atlas_with_gutter(2048, 8).get_mip_level_count(), 3);
+    }
+
+    #[test]
+    fn mip_levels_stop_at_one_pixel() {
+        assert_eq!(atlas_with_gutter(4, 64).get_mip_level_count(), 2);
+    }
 
-    pub fn print_skyline(&self) {
-        println!("{:?}", self.skyline);
+    #[test]
+    fn mip_levels_follow_smallest_region_gutter() {
+        let mut atlas = atlas_with_gutter(256, 2);
+        atlas.get_region(16, 16).unwrap();
+        atlas.set_gutter(8);
+        atlas.get_region(16, 16).unwrap();
+        assert_eq!(atlas.get_mip_level_count(), 1);
     }
 }